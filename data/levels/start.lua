@@ -1,5 +1,5 @@
-local function test (inputs)
-    return inputs
+local function test (outputs, inputs)
+    return outputs == inputs
 end
 
 local table = {true, false, nil}
@@ -0,0 +1,24 @@
+-- fires every number marble back out twice as big, and bits straight through
+module.name = "Doubler"
+module.identifier = "doubler.lua"
+module.body = "small"
+module.inputs = { -180 }
+module.outputs = { 0 }
+
+-- a marble came in, wait a bit before doing anything with it
+function module.update(inputs)
+    if inputs[1] ~= nil then
+        return { callback(0.15) }
+    end
+end
+
+function module.callback(inputs)
+    local marble = inputs[1]
+    if marble == nil then
+        return
+    end
+    if type(marble) == "number" then
+        marble = marble * 2
+    end
+    return { take(1), fire(marble, 1) }
+end
@@ -0,0 +1,74 @@
+//! a module pack with one module and one level in it, `cargo run --example pack` plays the game
+//! with it added
+
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use marble_machine::{
+    MarbleMachinePlugin,
+    engine::module_state::ModuleState,
+    modules::{
+        BodyType,
+        Module,
+        ModuleEventSender,
+        ModuleInfo,
+        ModuleRegistry,
+        ModuleUpdate::*,
+        SpawnInstructions,
+    },
+    pack::{ ModulePack, PackPlugin },
+};
+use serde::{ Serialize, Deserialize };
+
+/// fires every marble that goes into it out of both of its outputs
+#[derive(Clone, Default, Component, Serialize, Deserialize, Debug)]
+struct Splitter;
+
+#[typetag::serde]
+impl Module for Splitter {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_input_rotations([-180.0].into_iter(), 0.0)
+                .with_output_rotations([-45.0, 45.0].into_iter(), 0.0),
+            name: "Splitter",
+            identifier: "splitter.example",
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state[0].is_some() {
+            events.send(Callback(0.15));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if let Some(marble) = state.input_state[0].take() {
+            events.send(FireMarbleFrom(marble, 0, 1.0));
+            events.send(FireMarbleFrom(marble, 1, 1.0));
+            events.send(UpdateIndicatorColors);
+        }
+    }
+}
+
+struct ExamplePack;
+
+impl ModulePack for ExamplePack {
+    fn modules(&self, registry: &mut ModuleRegistry) {
+        registry.register_default::<Splitter>(Some("Example"));
+    }
+
+    fn level_dirs(&self) -> Vec<PathBuf> {
+        vec![PathBuf::from("examples/pack/levels")]
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        .add_plugin(MarbleMachinePlugin)
+        .add_plugin(PackPlugin(ExamplePack))
+        .run();
+}
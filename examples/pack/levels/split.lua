@@ -0,0 +1,22 @@
+-- every bit has to come out of both outputs, the splitter from this pack makes short work of it
+local cases = { true, false, true }
+
+local function test(outputs, input)
+    return outputs[1] == input and outputs[2] == input
+end
+
+local function generate(i)
+    return cases[i]
+end
+
+level.test = test
+level.generate = generate
+level.inputs = { "bit" }
+level.outputs = { "bit", "bit" }
+
+level.name = "Split"
+level.description = [[
+Get every marble from the input to both of the outputs.
+
+The Splitter in the Example section fires whatever goes into it out of both sides.
+]]
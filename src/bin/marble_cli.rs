@@ -0,0 +1,115 @@
+//! `marble_cli [--json] [--modules <dir>] <level.lua> <save.ron>...` runs every save through a level
+//! without opening a window and reports how each one did. lua modules only get loaded from the
+//! folder given with `--modules`. exits with 1 if any save failed a case and 2 if any of them
+//! couldnt be run at all
+
+use marble_machine::{ game::verify::{ self, VerifyReport }, modules::ModuleRegistry };
+use serde::Serialize;
+
+const USAGE: &str = "usage: marble_cli [--json] [--modules <dir>] <level.lua> <save.ron>...";
+
+/// how a single save did
+#[derive(Serialize)]
+struct SaveReport {
+    save: String,
+    passed: bool,
+    #[serde(flatten)]
+    report: Option<VerifyReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl SaveReport {
+    fn exit_code(&self) -> i32 {
+        match self.report {
+            Some(_) => i32::from(!self.passed),
+            None => 2,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    level: String,
+    passed: bool,
+    saves: Vec<SaveReport>,
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let json = args.iter().any(|arg| arg == "--json");
+    args.retain(|arg| arg != "--json");
+    let modules_dir = match args.iter().position(|arg| arg == "--modules") {
+        Some(i) if i + 1 < args.len() => {
+            let dir = args.remove(i + 1);
+            args.remove(i);
+            Some(dir)
+        }
+        Some(_) => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+        None => None,
+    };
+    let [level, saves @ ..] = args.as_slice() else {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    };
+    if saves.is_empty() {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    }
+
+    let registry = match modules_dir {
+        Some(dir) => ModuleRegistry::with_scripts(dir),
+        None => ModuleRegistry::with_builtin(),
+    };
+
+    let saves = saves
+        .iter()
+        .map(|save| match verify::verify_level(level, save, &registry) {
+            Ok(report) =>
+                SaveReport {
+                    save: save.clone(),
+                    passed: report.passed(),
+                    report: Some(report),
+                    error: None,
+                },
+            Err(e) =>
+                SaveReport {
+                    save: save.clone(),
+                    passed: false,
+                    report: None,
+                    error: Some(e.to_string()),
+                },
+        })
+        .collect::<Vec<_>>();
+    let code = saves.iter().map(SaveReport::exit_code).max().unwrap_or(0);
+
+    if json {
+        let report = Report {
+            level: level.clone(),
+            passed: code == 0,
+            saves,
+        };
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        for save in saves.iter() {
+            println!("{}", save.save);
+            match (&save.report, &save.error) {
+                (Some(report), _) => {
+                    if let Some(load) = report.load.as_ref().filter(|load| !load.is_clean()) {
+                        println!("{load}");
+                    }
+                    println!("{report}\n");
+                }
+                (None, Some(e)) => println!("couldnt run it: {e}\n"),
+                (None, None) => {}
+            }
+        }
+        let passed = saves.iter().filter(|save| save.passed).count();
+        println!("{passed}/{} saves passed", saves.len());
+    }
+
+    std::process::exit(code);
+}
@@ -103,6 +103,7 @@ where
                     ),
                     TransformBundle::from_transform(instruction.child()),
                     // RigidBody::Fixed,
+                    marker::Muzzle,
                 ))
                 .name("out.collider")
                 .id(),
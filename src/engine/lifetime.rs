@@ -1,6 +1,8 @@
 use crate::{query::QueryQuerySimple, *};
 
-/// a struct that stores the number of ticks left until it gets borked
+use super::sim::SimClock;
+
+/// a struct that stores the number of sim ticks left until it gets borked
 #[derive(Deref, DerefMut, Component)]
 pub struct Lifetime(pub usize);
 
@@ -10,11 +12,16 @@ pub fn update_lifetime(
     mut commands: Commands,
     mut lifetime: ParamSet<(Query<Entity, Added<Lifetime>>, Query<&mut Lifetime>)>,
     mut entities: Local<Vec<Entity>>,
+    clock: Res<SimClock>,
 ) {
     for entity in lifetime.p0().iter() {
         entities.push(entity);
         // dbg!("e");
     }
+    // lifetimes are in sim ticks
+    if !clock.ticked() {
+        return;
+    }
 
     let mut q_lifetime = lifetime.p1();
     // filter out entities that may not exist anymore
@@ -23,7 +30,7 @@ pub fn update_lifetime(
     entities
         .drain_filter(|&mut e| {
             let mut lifetime = q_lifetime.entity_mut(e);
-            **lifetime -= 1;
+            **lifetime = lifetime.saturating_sub(clock.delta() as usize);
             **lifetime == 0
         })
         .for_each(|e| commands.entity(e).despawn());
@@ -1,12 +1,44 @@
-use crate::*;
+use std::fmt;
 
-#[derive(Clone, Copy, Debug)]
+use serde::{ Serialize, Deserialize };
+
+use crate::{ *, atlas::{ basic, AtlasDictionary } };
+
+/// how big every marble is to the physics, whatever its sprite looks like, so the type of a
+/// marble never changes where it goes. the size of `marble_small`
+pub const MARBLE_RADIUS: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum MarbleType {
     Bit,
     Num,
 }
 
-#[derive(Copy, Clone, Debug, Component)]
+impl MarbleType {
+    /// the sprite marbles of this type get drawn with
+    pub fn sprite(self) -> basic {
+        match self {
+            MarbleType::Bit => basic::marble_small,
+            MarbleType::Num => basic::marble,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            MarbleType::Bit => Color::GREEN,
+            MarbleType::Num => Color::rgb_u32(0xf2c14e),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MarbleType::Bit => "bit.marble",
+            MarbleType::Num => "num.marble",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Component, Serialize, Deserialize)]
 pub struct Marble {
     ty: MarbleType,
     val: i32,
@@ -27,6 +59,11 @@ impl Marble {
     pub fn get_val(&self) -> i32 {
         self.val
     }
+
+    /// the value of this marble as a bit, anything nonzero is on
+    pub fn get_bit(&self) -> bool {
+        self.val != 0
+    }
     
     pub fn bit(val: bool) -> Self {
         Self {
@@ -34,6 +71,41 @@ impl Marble {
             val: val as i32,
         }
     }
+
+    pub fn num(val: i32) -> Self {
+        Self {
+            ty: MarbleType::Num,
+            val,
+        }
+    }
+}
+
+impl fmt::Display for Marble {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ty {
+            MarbleType::Bit => write!(f, "bit {}", self.get_bit() as u8),
+            MarbleType::Num => write!(f, "{}", self.val),
+        }
+    }
+}
+
+/// give new marbles a sprite, the simulation doesnt need one so it only happens when theres
+/// something to draw them with
+pub fn marble_sprites(mut commands: Commands, q_marbles: Query<(Entity, &Marble), Added<Marble>>) {
+    for (entity, marble) in q_marbles.iter() {
+        let ty = marble.get_type();
+        let (texture_atlas, index) = ty.sprite().info();
+        commands.entity(entity).insert((
+            texture_atlas,
+            TextureAtlasSprite {
+                index,
+                color: ty.color(),
+                anchor: Anchor::Center,
+                ..default()
+            },
+            VisibilityBundle::default(),
+        ));
+    }
 }
 
 /// despawn marbles if they go too low (and should be despawned cuz theyre out of bounds)
@@ -4,12 +4,10 @@ use crate::{
     *,
     graphics::grid::GridInfo,
 };
-use atlas::{ basic, AtlasDictionary };
 use marble::Marble;
 use rand::Rng;
-use spawn::CommandsSpawn;
 
-use super::{lifetime::Lifetime, module_state::ModuleState};
+use super::{lifetime::Lifetime, module_state::ModuleState, sim::SimRng};
 
 /// an event that tells the program to fire a marble from this marble output.
 #[derive(Copy, Clone)]
@@ -31,46 +29,52 @@ impl FireMarbleEvent {
 
 pub const VELOCITY_FACTOR: f32 = 120.0;
 
+/// how many marbles have been fired since the machine started running
+#[derive(Resource, Default, Debug)]
+pub struct MarblesFired(pub usize);
+
 /// if any `SpawnMarbles` events have fired, fire a marble at the specified entity with the
 /// right power and such and such.
+#[allow(clippy::too_many_arguments)]
 pub fn fire_marbles(
     mut commands: Commands,
     mut spawn_events: EventReader<FireMarbleEvent>,
     q_global_transform: Query<&GlobalTransform>,
     q_children: Query<&Children>,
-    w_sprite: Query<Entity, With<TextureAtlasSprite>>,
-    grid_info: Res<GridInfo>
+    w_muzzle: Query<Entity, With<marker::Muzzle>>,
+    grid_info: Res<GridInfo>,
+    mut rng: ResMut<SimRng>,
+    mut fired: ResMut<MarblesFired>
 ) {
     for event in spawn_events.iter() {
         let mut transform = q_global_transform
-            .entity(q_children.entity(event.from).iter().with(&w_sprite).next().unwrap())
+            .entity(q_children.entity(event.from).iter().with(&w_muzzle).next().unwrap())
             .compute_transform();
         transform.translation.z = 0.0;
         let pos = transform.translation;
         if !grid_info.in_bounds(pos.truncate()) {
             continue;
         }
+        fired.0 += 1;
 
         // dbg!(rotation.mul_vec3(Vec3::X).truncate() * 120.0);
+        let ty = event.marble.get_type();
+        // no sprite, marble::marble_sprites adds one when theres something to draw it
         commands
-            .spawn_atlas_sprite(
-                basic::marble_small,
-                Color::GREEN,
-                Transform::from_translation(pos + -pos.z + ZOrder::Marble)
-            )
+            .spawn(TransformBundle::from_transform(Transform::from_translation(pos + -pos.z + ZOrder::Marble)))
             .insert((
-                Collider::ball(basic::marble_small.width() * 0.5),
+                Collider::ball(marble::MARBLE_RADIUS),
                 RigidBody::Dynamic,
                 Velocity {
-                    linvel: transform.rotation.mul_vec3(Vec3::X).truncate() * VELOCITY_FACTOR,
-                    angvel: rand::thread_rng().gen_range(-10.0..10.0),
+                    linvel: transform.rotation.mul_vec3(Vec3::X).truncate() * VELOCITY_FACTOR * event.power,
+                    angvel: rng.gen_range(-10.0..10.0),
                 },
                 ColliderMassProperties::Mass(1.0),
                 Restitution::coefficient(0.9),
                 Lifetime(1200),
             ))
             .insert(event.marble)
-            .name("bit.marble");
+            .name(ty.name());
     }
 }
 
@@ -101,7 +105,13 @@ pub fn update_inputs(
                 let marble_e = e2;
                 let marble = *q_marble.entity(e2);
 
-                let parent = q_parent.entity(q_parent.entity(e1).get()).get();
+                // the module is somewhere above the input, however deep it ended up
+                let parent = std::iter
+                    ::successors(Some(e1), |e| q_parent.get(*e).ok().map(|p| p.get()))
+                    .find(|e| q_state.contains(*e));
+                let Some(parent) = parent else {
+                    return;
+                };
                 let input_state = &mut q_state.entity_mut(parent).input_state;
 
                 // if the input is not occupied, despawn the marble and update input_state
@@ -6,19 +6,30 @@ pub mod marble;
 pub mod marble_io;
 pub mod spawn;
 pub mod module_state;
+pub mod piece;
+pub mod sim;
+
+use sim::{ AppSimExt, TickStage };
 
 pub fn app(app: &mut App) {
-    app.add_system(lifetime::update_lifetime)
+    app.init_resource::<sim::SimClock>()
+        .init_resource::<sim::SimRng>()
+        .init_resource::<sim::SimControl>()
+        .init_resource::<spawn::EditorIds>()
+        .add_stage_after(CoreStage::Update, sim::SimTick, sim::SimTickStage::default())
         .add_system_set_to_stage(
             CoreStage::PreUpdate,
             SystemSet::new()
                 .with_system(spawn::spawn_modules.label("spawn::spawn_modules"))
-                .with_system(marble_io::fire_marbles)
+                // a pasted piece gets placed before the modules following it show up
+                .with_system(piece::spawn_pieces.label("spawn::spawn_modules").before(spawn::spawn_modules))
         )
-        .add_system_set_to_stage(
-            CoreStage::Update,
+        .add_tick_system_set(TickStage::Fire, SystemSet::new().with_system(marble_io::fire_marbles))
+        .add_tick_system_set(
+            TickStage::Logic,
             SystemSet::new()
+                .with_system(lifetime::update_lifetime)
                 .with_system(marble::despawn_marbles)
-                .with_system(marble_io::update_inputs)
+                .with_system(marble_io::update_inputs.label("marble_io::update_inputs"))
         );
 }
\ No newline at end of file
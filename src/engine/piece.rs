@@ -0,0 +1,221 @@
+use std::f32::consts::PI;
+
+use serde::{ Serialize, Deserialize };
+
+use crate::{ *, engine::spawn::{ EditorId, EditorIds } };
+
+/// how thick walls and such are
+const THICKNESS: f32 = 2.0;
+/// how big bumpers are
+const BUMPER_RADIUS: f32 = 4.0;
+
+/// passive bits of geometry that arent modules, just there for marbles to bounce off of
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PieceKind {
+    Wall,
+    /// a quarter circle
+    Ramp,
+    /// really bouncy
+    Bumper,
+    /// two walls in a V with a gap at the bottom
+    Funnel,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 4] = [PieceKind::Wall, PieceKind::Ramp, PieceKind::Bumper, PieceKind::Funnel];
+
+    pub fn name(self) -> &'static str {
+        use PieceKind::*;
+        match self {
+            Wall => "Wall",
+            Ramp => "Ramp",
+            Bumper => "Bumper",
+            Funnel => "Funnel",
+        }
+    }
+
+    /// the lines its made out of, for drawing it and for its collider (bumpers are just a circle)
+    pub fn outline(self) -> Vec<Vec<Vec2>> {
+        use PieceKind::*;
+        match self {
+            Wall => vec![vec![Vec2::new(-8.0, 0.0), Vec2::new(8.0, 0.0)]],
+            Ramp => {
+                let segments = 8;
+                let center = Vec2::new(8.0, 8.0);
+                let arc = (0..=segments)
+                    .map(|i| {
+                        let angle = PI + (PI / 2.0) * (i as f32) / (segments as f32);
+                        center + Vec2::from_angle(angle) * 16.0
+                    })
+                    .collect();
+                vec![arc]
+            }
+            Bumper => vec![],
+            Funnel =>
+                vec![
+                    vec![Vec2::new(-12.0, 8.0), Vec2::new(-3.5, -4.0)],
+                    vec![Vec2::new(12.0, 8.0), Vec2::new(3.5, -4.0)]
+                ],
+        }
+    }
+
+    pub fn collider(self) -> Collider {
+        if self == PieceKind::Bumper {
+            return Collider::ball(BUMPER_RADIUS);
+        }
+        // a capsule for every segment of every line
+        let shapes = self
+            .outline()
+            .iter()
+            .flat_map(|line| line.windows(2))
+            .map(|segment| {
+                let (a, b) = (segment[0], segment[1]);
+                let half = (b - a).length() / 2.0;
+                let angle = (b - a).y.atan2((b - a).x);
+                ((a + b) / 2.0, angle, Collider::capsule_x(half, THICKNESS / 2.0))
+            })
+            .collect();
+        Collider::compound(shapes)
+    }
+
+    pub fn restitution(self) -> f32 {
+        match self {
+            PieceKind::Bumper => 1.5,
+            _ => 0.8,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            PieceKind::Bumper => Color::hsl(350.0, 0.55, 0.6),
+            _ => Color::hsl(216.0, 0.25, 0.36),
+        }
+    }
+
+    fn shape(self, transform: Transform) -> ShapeBundle {
+        let stroke = StrokeMode {
+            color: self.color(),
+            options: StrokeOptions::default()
+                .with_line_cap(LineCap::Round)
+                .with_line_join(LineJoin::Round)
+                .with_line_width(THICKNESS)
+                .with_tolerance(0.01),
+        };
+        if self == PieceKind::Bumper {
+            let circle = shapes::Circle { radius: BUMPER_RADIUS - 0.5, center: Vec2::ZERO };
+            let draw_mode = DrawMode::Outlined {
+                fill_mode: FillMode::color(self.color()),
+                outline_mode: StrokeMode::new(Color::WHITE, 1.0),
+            };
+            return GeometryBuilder::build_as(&circle, draw_mode, transform);
+        }
+
+        let mut path = PathBuilder::new();
+        for line in self.outline() {
+            let mut points = line.into_iter();
+            let Some(first) = points.next() else {
+                continue;
+            };
+            path.move_to(first);
+            points.for_each(|p| {
+                path.line_to(p);
+            });
+        }
+        GeometryBuilder::build_as(&path.build(), DrawMode::Stroke(stroke), transform)
+    }
+}
+
+/// everything needed to spawn a piece back in
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PieceInfo {
+    pub kind: PieceKind,
+    pub offset: Vec3,
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+impl PieceInfo {
+    pub fn new(kind: PieceKind) -> Self {
+        Self { kind, offset: Vec3::ZERO, rotation: 0.0 }
+    }
+
+    /// a snapshot of a piece as it is right now
+    pub fn of(kind: PieceKind, transform: &Transform) -> Self {
+        Self {
+            kind,
+            offset: transform.translation,
+            rotation: transform.rotation.to_euler(EulerRot::XYZ).2,
+        }
+    }
+}
+
+/// spawn a piece, see [`spawn_pieces`]
+pub struct SpawnPiece {
+    info: PieceInfo,
+    // whether its going to be dragged around
+    place: bool,
+    // whether it gets dragged around along with whatever is being placed
+    follow: bool,
+    // the id to give it, if it had one before
+    id: Option<EditorId>,
+}
+
+impl SpawnPiece {
+    pub fn new(info: PieceInfo) -> Self {
+        Self { info, place: false, follow: false, id: None }
+    }
+
+    /// this piece is going to be dragged around
+    pub fn place(mut self) -> Self {
+        self.place = true;
+        self
+    }
+
+    /// this piece gets dragged around along with the last thing sent to be placed, keeping its
+    /// offset from it
+    pub fn follow(mut self) -> Self {
+        self.follow = true;
+        self
+    }
+
+    /// spawn it with this id instead of a fresh one
+    pub fn with_id(mut self, id: EditorId) -> Self {
+        self.id = Some(id);
+        self
+    }
+}
+
+pub fn spawn_pieces(
+    mut commands: Commands,
+    mut spawn_events: EventReader<SpawnPiece>,
+    mut selected: ResMut<SelectedModules>,
+    mut ids: ResMut<EditorIds>
+) {
+    for SpawnPiece { info: PieceInfo { kind, offset, rotation }, place, follow, id } in spawn_events.iter() {
+        let transform = Transform::from_translation(*offset).with_rotation(
+            Quat::from_rotation_z(*rotation)
+        );
+        let visibility = if *place || *follow { Visibility::INVISIBLE } else { Visibility::VISIBLE };
+        let shape = commands
+            .spawn(kind.shape(Transform::from_xyz(0.0, 0.0, ZOrder::BodyComponent.f32())))
+            .insert((
+                kind.collider(),
+                RigidBody::Fixed,
+                Restitution::coefficient(kind.restitution()),
+            ))
+            .name("shape.piece")
+            .id();
+        let piece = commands
+            .spawn(SpatialBundle { transform, visibility, ..default() })
+            .insert((*kind, id.unwrap_or_else(|| ids.next())))
+            .name(kind.name())
+            .add_child(shape)
+            .id();
+
+        if *place {
+            *selected = SelectedModules::place_entity(piece);
+        } else if *follow {
+            selected.group.push(piece);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use rand::{ rngs::StdRng, SeedableRng };
+
+use crate::*;
+
+/// how long one tick of the simulation is, same as the physics timestep
+pub const TICK: f32 = 1.0 / 60.0;
+
+/// what the rng gets seeded with whenever the simulation is reset
+const SEED: u64 = 0x6d61_7262_6c65;
+
+/// how many ticks it takes for `secs` seconds of simulation time to pass
+pub fn ticks(secs: f32) -> u64 {
+    (secs / TICK).round() as u64
+}
+
+/// the most ticks we'll ever squeeze into one frame
+pub const MAX_TICKS_PER_FRAME: u32 = 16;
+
+/// the speeds you can fast forward at
+pub const SPEEDS: [u32; 4] = [1, 2, 4, 16];
+
+/// the clock the simulation runs on, it moves forward once for every physics step rather than with
+/// real time, so everything that depends on it stays in lockstep with rapier
+#[derive(Resource, Default, Debug)]
+pub struct SimClock {
+    tick: u64,
+    /// whether this run of the [`SimTick`] stage is a tick, `0` while paused
+    delta: u64,
+}
+
+impl SimClock {
+    /// how many ticks have passed in total
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// how many ticks the physics just stepped, one while playing and zero while paused. stuff
+    /// that runs on the clock should move forward by this much
+    pub fn delta(&self) -> u64 {
+        self.delta
+    }
+
+    /// whether the physics just stepped
+    pub fn ticked(&self) -> bool {
+        self.delta > 0
+    }
+}
+
+/// play / pause / step / fast forward
+#[derive(Resource, Debug)]
+pub struct SimControl {
+    pub paused: bool,
+    /// ticks per frame while playing
+    pub speed: u32,
+    /// ticks left to step through while paused
+    steps: u32,
+}
+
+impl Default for SimControl {
+    fn default() -> Self {
+        Self { paused: false, speed: 1, steps: 0 }
+    }
+}
+
+impl SimControl {
+    pub fn toggle(&mut self) {
+        self.paused = !self.paused;
+        self.steps = 0;
+    }
+
+    /// pause and step forward `n` ticks
+    pub fn step(&mut self, n: u32) {
+        self.paused = true;
+        self.steps += n;
+    }
+
+    /// ticks left to step through
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// how many ticks to run this frame
+    fn take_ticks(&mut self) -> u32 {
+        if self.paused {
+            let ticks = self.steps.min(MAX_TICKS_PER_FRAME);
+            self.steps -= ticks;
+            ticks
+        } else {
+            self.speed.clamp(1, MAX_TICKS_PER_FRAME)
+        }
+    }
+}
+
+/// the rng anything in the simulation should use instead of `thread_rng`
+#[derive(Resource, Deref, DerefMut)]
+pub struct SimRng(StdRng);
+
+impl Default for SimRng {
+    fn default() -> Self {
+        Self(StdRng::seed_from_u64(SEED))
+    }
+}
+
+impl SimRng {
+    /// start over from the seed so the same machine does the same thing again
+    pub fn reset(&mut self) {
+        *self = default();
+    }
+}
+
+/// where everything that happens once per tick goes, see [`SimTickStage`]
+#[derive(StageLabel, Debug)]
+pub struct SimTick;
+
+/// the stages of a single tick, in the order they run
+#[derive(StageLabel, Clone, Copy, Debug)]
+pub enum TickStage {
+    /// rapier forgets about anything that was despawned last tick
+    DetectDespawn,
+    /// marbles fired last tick get spawned in
+    Fire,
+    /// rapier catches up with the world, steps, and writes the results back
+    SyncBackend,
+    StepSimulation,
+    Writeback,
+    /// inputs pick up marbles, modules and lifetimes do their thing
+    Logic,
+}
+
+/// runs the whole simulation, physics and everything that reacts to it, once for every tick this
+/// frame. fast forwarding runs it more times rather than taking bigger steps, so a machine does
+/// the exact same thing at any speed. while paused it still runs once without stepping so rapier
+/// keeps up with whatever got moved or spawned
+pub struct SimTickStage {
+    schedule: Schedule,
+}
+
+impl Default for SimTickStage {
+    fn default() -> Self {
+        use TickStage::*;
+        let rapier = |stage| {
+            SystemStage::parallel().with_system_set(RapierPhysicsPlugin::<NoUserData>::get_systems(stage))
+        };
+        let mut schedule = Schedule::default();
+        schedule
+            .add_stage(DetectDespawn, rapier(PhysicsStages::DetectDespawn))
+            .add_stage(Fire, SystemStage::parallel())
+            .add_stage(SyncBackend, rapier(PhysicsStages::SyncBackend))
+            .add_stage(StepSimulation, rapier(PhysicsStages::StepSimulation))
+            .add_stage(Writeback, rapier(PhysicsStages::Writeback))
+            .add_stage(Logic, SystemStage::parallel());
+        Self { schedule }
+    }
+}
+
+impl SimTickStage {
+    pub fn add_system_set_to_stage(&mut self, stage: TickStage, set: SystemSet) -> &mut Self {
+        self.schedule.add_system_set_to_stage(stage, set);
+        self
+    }
+}
+
+impl Stage for SimTickStage {
+    fn run(&mut self, world: &mut World) {
+        let ticks = world.resource_mut::<SimControl>().take_ticks();
+        world.resource_mut::<RapierConfiguration>().physics_pipeline_active = ticks > 0;
+
+        for _ in 0..ticks.max(1) {
+            let delta = u64::from(ticks > 0);
+            let mut clock = world.resource_mut::<SimClock>();
+            clock.delta = delta;
+            clock.tick += delta;
+            self.schedule.run(world);
+        }
+    }
+}
+
+/// add systems to one of the stages of a tick
+pub trait AppSimExt {
+    fn add_tick_system_set(&mut self, stage: TickStage, set: SystemSet) -> &mut Self;
+}
+
+impl AppSimExt for App {
+    fn add_tick_system_set(&mut self, stage: TickStage, set: SystemSet) -> &mut Self {
+        self.stage(SimTick, |sim: &mut SimTickStage| sim.add_system_set_to_stage(stage, set))
+    }
+}
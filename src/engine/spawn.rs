@@ -1,8 +1,8 @@
 use crate::{
-    modules::{ SpawnInstructions, ModuleComponent, Module },
+    modules::{ SpawnInstructions, ModuleComponent, Module, ModuleParam },
     *,
     engine::module_state::ModuleState,
-    game::save_load::ModuleInfo,
+    game::{ save_load::ModuleInfo, level::LevelPort },
 };
 use atlas::AtlasDictionary;
 use bevy::ecs::system::EntityCommands;
@@ -68,10 +68,32 @@ impl<'a, 'b> CommandsSpawn<'a, 'b> for Commands<'a, 'b> {
     }
 }
 
+/// a stable id for a module, unlike its `Entity` it stays the same when undo / redo despawns the
+/// module and spawns it back in
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EditorId(pub u64);
+
+/// hands out fresh [`EditorId`]s
+#[derive(Resource, Default, Debug)]
+pub struct EditorIds(u64);
+
+impl EditorIds {
+    pub fn next(&mut self) -> EditorId {
+        self.0 += 1;
+        EditorId(self.0)
+    }
+}
+
 pub struct SpawnModule {
     info: ModuleInfo,
     // whether this module is going to be dragged around
     place: bool,
+    // whether this module gets dragged around along with the one being placed
+    follow: bool,
+    // the level port this module stands in for, if any
+    port: Option<LevelPort>,
+    // the id to give it, if it had one before
+    id: Option<EditorId>,
 }
 
 impl SpawnModule {
@@ -79,6 +101,9 @@ impl SpawnModule {
         SpawnModule {
             info: ModuleInfo::new(module),
             place: false,
+            follow: false,
+            port: None,
+            id: None,
         }
     }
 
@@ -86,6 +111,9 @@ impl SpawnModule {
         SpawnModule {
             info,
             place: false,
+            follow: false,
+            port: None,
+            id: None,
         }
     }
 
@@ -94,19 +122,44 @@ impl SpawnModule {
         self.place = true;
         self
     }
+
+    /// this module gets dragged around along with the last one sent with [`SpawnModule::place`],
+    /// keeping its offset from it
+    pub fn follow(mut self) -> Self {
+        self.follow = true;
+        self
+    }
+
+    /// this module stands in for one of the level's inputs or outputs
+    pub fn port(mut self, port: LevelPort) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// spawn it with this id instead of a fresh one
+    pub fn with_id(mut self, id: EditorId) -> Self {
+        self.id = Some(id);
+        self
+    }
 }
 
 /// spawn a module based on [`SpawnModule`] events fired
 pub fn spawn_modules(
     mut commands: Commands,
     mut spawn_events: EventReader<SpawnModule>,
-    mut selected: ResMut<SelectedModules>
+    mut selected: ResMut<SelectedModules>,
+    mut ids: ResMut<EditorIds>
 ) {
     for event in spawn_events.iter() {
-        let SpawnModule { info: ModuleInfo { module, instructions, module_type, offset }, place } =
-            event;
+        let SpawnModule {
+            info: ModuleInfo { module, instructions, module_type, offset, blueprint },
+            place,
+            follow,
+            port,
+            id,
+        } = event;
 
-        let mut sprite = if *place {
+        let mut sprite = if *place || *follow {
             SpriteBundle {
                 visibility: Visibility::INVISIBLE,
                 ..default()
@@ -115,10 +168,17 @@ pub fn spawn_modules(
             SpriteBundle::default()
         };
         sprite.transform.translation = *offset;
+        // saves could have anything in them
+        let mut module = module.clone();
+        module.params().iter_mut().for_each(ModuleParam::validate);
         let parent = commands
             .spawn(sprite)
-            .name(module_type.get_identifier())
-            .insert((ModuleComponent { ty: *module_type, module: module.clone() }, marker::Module))
+            .name(module_type.get_identifier().to_string())
+            .insert((
+                ModuleComponent { ty: module_type.clone(), module },
+                marker::Module,
+                id.unwrap_or_else(|| ids.next()),
+            ))
             .id();
         let mut children: Vec<Entity> = vec![];
 
@@ -131,7 +191,12 @@ pub fn spawn_modules(
             $($tail:tt)*
         ) {
             let body = commands
-                    .spawn_atlas_sprite($body_type.sprite(), $body_type.color(), Transform::from_xyz(0.0, 0.0, ZOrder::BodyComponent.f32()))
+                    .spawn_atlas_sprite(
+                        $body_type.sprite(),
+                        $body_type.color(),
+                        Transform::from_xyz(0.0, 0.0, ZOrder::BodyComponent.f32())
+                            .with_rotation(Quat::from_rotation_z(*rotation))
+                    )
                     .insert((
                         $body_type.collider(),
                         RigidBody::Fixed,
@@ -148,6 +213,7 @@ pub fn spawn_modules(
         // run through all the instructions laid out in the module
         let SpawnInstructions {
             body,
+            rotation,
             inputs: input_transforms,
             outputs: output_transforms,
         } = instructions;
@@ -157,7 +223,15 @@ pub fn spawn_modules(
             body @ BodyType::Small => {
                 spawn_body!(body, "body_small.component");
             }
-            _ => todo!(),
+            body @ BodyType::Large => {
+                spawn_body!(body, "body.component");
+            }
+            body @ BodyType::Wide => {
+                spawn_body!(body, "body_wide.component");
+            }
+            body @ BodyType::Block => {
+                spawn_body!(body, "body_block.component");
+            }
         }
 
         // inputs
@@ -186,8 +260,24 @@ pub fn spawn_modules(
 
         commands.entity(parent).push_children(&children).insert(state);
 
+        if let Some(tag) = blueprint {
+            commands.entity(parent).insert(tag.clone());
+        }
+
+        match port {
+            Some(LevelPort::Input(n)) => {
+                commands.entity(parent).insert((marker::LevelInput(*n), marker::Locked));
+            }
+            Some(LevelPort::Output(n)) => {
+                commands.entity(parent).insert((marker::LevelOutput(*n), marker::Locked));
+            }
+            None => {}
+        }
+
         if *place {
             *selected = SelectedModules::place_entity(parent);
+        } else if *follow {
+            selected.group.push(parent);
         } else {
             *selected = SelectedModules::from_entity(parent);
         }
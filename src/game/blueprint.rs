@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+
+use serde::{ Serialize, Deserialize };
+
+use crate::{
+    *,
+    misc::RapierContextMethods,
+    modules::{ BodyType, ModuleRegistry, SpawnInstructions },
+    engine::spawn::{ EditorId, EditorIds, SpawnModule },
+    game::{ save_load::{ ModuleInfo, ModuleInfoQuery }, save_file::{ self, BlueprintFile } },
+    interactive::history::{ RecordEdit, Edit },
+};
+
+/// where blueprints live, one ron file each
+pub const BLUEPRINT_DIR: &str = "data/blueprints";
+
+/// which blueprint a module was spawned from and which part of it it is
+#[derive(Component, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlueprintTag {
+    pub name: String,
+    /// every copy of a blueprint gets its own instance so they can be told apart
+    pub instance: u64,
+    /// the index of this module in [`Blueprint::modules`]
+    pub index: usize,
+}
+
+/// a port on one of the modules in a blueprint
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PortRef {
+    pub module: usize,
+    pub port: usize,
+}
+
+/// a bunch of modules packaged up to be reused, the ports in `inputs` and `outputs` are the ones
+/// meant to be hooked up to stuff outside of it
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Blueprint {
+    /// offsets are relative to the first module
+    pub modules: Vec<ModuleInfo>,
+    pub inputs: Vec<PortRef>,
+    pub outputs: Vec<PortRef>,
+}
+
+impl Blueprint {
+    /// what it looks like in the palette, a big body with the exposed ports around it (inputs on
+    /// the left, outputs on the right)
+    pub fn preview(&self) -> SpawnInstructions {
+        let spread = |n: usize, center: f32| {
+            let step = 150.0 / n.max(1) as f32;
+            (0..n).map(move |i| center + step * (i as f32 - (n as f32 - 1.0) / 2.0))
+        };
+        SpawnInstructions::from_body(BodyType::Large)
+            .with_input_rotations(spread(self.inputs.len(), 180.0), 0.0)
+            .with_output_rotations(spread(self.outputs.len(), 0.0), 0.0)
+    }
+
+    /// the modules for a new copy of this blueprint with its first module at `anchor`
+    pub fn instantiate(&self, name: &str, instance: u64, anchor: Vec3) -> Vec<ModuleInfo> {
+        self.modules
+            .iter()
+            .enumerate()
+            .map(|(index, module)| {
+                let mut module = module.clone();
+                module.offset += anchor;
+                module.blueprint = Some(BlueprintTag { name: name.to_string(), instance, index });
+                module
+            })
+            .collect()
+    }
+}
+
+/// a new instance id, random so ones that came from saves dont clash with new ones
+pub fn new_instance() -> u64 {
+    rand::random()
+}
+
+/// give every blueprint instance in `modules` a new instance id, so pasting a copy of one doesnt
+/// end up being part of the original
+pub fn fresh_instances(modules: &mut [ModuleInfo]) {
+    let mut instances = HashMap::new();
+    for tag in modules.iter_mut().filter_map(|m| m.blueprint.as_mut()) {
+        tag.instance = *instances.entry(tag.instance).or_insert_with(new_instance);
+    }
+}
+
+/// every blueprint we know about, by name
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct Blueprints(BTreeMap<String, Blueprint>);
+
+/// load every blueprint in [`BLUEPRINT_DIR`]
+pub fn load_blueprints(mut blueprints: ResMut<Blueprints>, registry: Res<ModuleRegistry>) {
+    let Ok(dir) = std::fs::read_dir(BLUEPRINT_DIR) else {
+        return;
+    };
+    for path in dir.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.extension().map_or(true, |ext| ext != "ron") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        match read_blueprint(&path, &registry) {
+            Ok(blueprint) => {
+                blueprints.insert(name.to_string(), blueprint);
+            }
+            Err(e) => error!("couldnt load blueprint {path:?}: {e}"),
+        }
+    }
+}
+
+fn read_blueprint(
+    path: impl AsRef<std::path::Path>,
+    registry: &ModuleRegistry
+) -> Result<Blueprint, LocatedError> {
+    let path = path.as_ref();
+    let serialized = std::fs::read_to_string(path)?;
+    let (blueprint, report) = save_file::parse_blueprint(&serialized, registry)?;
+    if !report.is_clean() {
+        warn!("loaded blueprint {path:?}: {report}");
+    }
+    Ok(blueprint)
+}
+
+fn write_blueprint(name: &str, blueprint: &Blueprint) -> Result<(), LocatedError> {
+    let serialized = ron::ser::to_string(&BlueprintFile::new(blueprint, name))?;
+    std::fs::create_dir_all(BLUEPRINT_DIR)?;
+    std::fs::write(format!("{BLUEPRINT_DIR}/{name}.ron"), serialized)?;
+    Ok(())
+}
+
+/// replace the blueprint called `name` in [`Blueprints`], `None` gets rid of it. this doesnt touch
+/// the files, so undo can use it without anything happening on disk
+pub fn set_blueprint(blueprints: &mut Blueprints, name: &str, blueprint: Option<Blueprint>) {
+    match blueprint {
+        Some(blueprint) => blueprints.insert(name.to_string(), blueprint),
+        None => blueprints.remove(name),
+    };
+}
+
+/// write the blueprint called `name` out to [`BLUEPRINT_DIR`] the way it is in [`Blueprints`]
+pub fn store_blueprint(blueprints: &Blueprints, name: &str) {
+    #[cfg(not(target_arch = "wasm32"))]
+    let written = match blueprints.get(name) {
+        Some(blueprint) => write_blueprint(name, blueprint),
+        None => std::fs::remove_file(format!("{BLUEPRINT_DIR}/{name}.ron")).map_err(LocatedError::from),
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Err(e) = written {
+        error!("couldnt write blueprint {name}: {e}");
+    }
+}
+
+/// spawn a copy of this blueprint in place mode
+pub struct SpawnBlueprint(pub String);
+
+pub fn spawn_blueprints(
+    mut events: EventReader<SpawnBlueprint>,
+    blueprints: Res<Blueprints>,
+    mut spawn_events: EventWriter<SpawnModule>,
+) {
+    for SpawnBlueprint(name) in events.iter() {
+        let Some(blueprint) = blueprints.get(name) else {
+            error!("no blueprint called {name}");
+            continue;
+        };
+        let mut modules = blueprint.instantiate(name, new_instance(), Vec3::ZERO).into_iter();
+        let Some(first) = modules.next() else {
+            continue;
+        };
+        spawn_events.send(SpawnModule::new(first).place());
+        for module in modules {
+            spawn_events.send(SpawnModule::new(module).follow());
+        }
+    }
+}
+
+/// turn these modules into a blueprint, replacing whatever had that name before
+pub struct SaveBlueprint {
+    pub name: String,
+    /// the first one is the anchor everything else is placed around
+    pub modules: Vec<Entity>,
+    /// (module, port index)
+    pub inputs: Vec<(Entity, usize)>,
+    pub outputs: Vec<(Entity, usize)>,
+}
+
+/// save blueprints to disk and rebuild every other copy of them to match, all of which is undone
+/// in one go. copies that wouldnt fit where they are anymore get left alone and detached instead
+#[allow(clippy::too_many_arguments)]
+pub fn save_blueprints(
+    mut commands: Commands,
+    rapier_ctx: Res<RapierContext>,
+    q_children: Query<&Children>,
+    has_collider: Query<With<Collider>>,
+    has_rigidbody: Query<With<RigidBody>>,
+    mut events: EventReader<SaveBlueprint>,
+    mut blueprints: ResMut<Blueprints>,
+    mut selected: ResMut<SelectedModules>,
+    q_info: ModuleInfoQuery,
+    q_instances: Query<(Entity, &BlueprintTag, &Transform, &EditorId)>,
+    mut ids: ResMut<EditorIds>,
+    mut spawn_events: EventWriter<SpawnModule>,
+    mut record: EventWriter<RecordEdit>,
+) {
+    for SaveBlueprint { name, modules, inputs, outputs } in events.iter() {
+        let mut infos = modules
+            .iter()
+            .filter_map(|e| q_info.get(*e))
+            .collect::<Vec<_>>();
+        if infos.len() != modules.len() || infos.is_empty() {
+            error!("couldnt save blueprint {name}, some of its modules are missing");
+            continue;
+        }
+        let anchor = infos[0].offset;
+        for info in infos.iter_mut() {
+            info.offset -= anchor;
+            info.blueprint = None;
+        }
+
+        let port_ref = |&(entity, port): &(Entity, usize)| {
+            Some(PortRef { module: modules.iter().position(|e| *e == entity)?, port })
+        };
+        let blueprint = Blueprint {
+            modules: infos,
+            inputs: inputs.iter().filter_map(port_ref).collect(),
+            outputs: outputs.iter().filter_map(port_ref).collect(),
+        };
+
+        let mut edits = vec![Edit::Blueprint {
+            name: name.clone(),
+            before: blueprints.get(name).cloned(),
+            after: Some(blueprint.clone()),
+        }];
+
+        // the modules it was made from are now a copy of it
+        let instance = new_instance();
+        for (index, &entity) in modules.iter().enumerate() {
+            let tag = BlueprintTag { name: name.clone(), instance, index };
+            edits.push(Edit::Retag {
+                entity,
+                before: q_instances.get(entity).ok().map(|(_, tag, ..)| tag.clone()),
+                after: Some(tag.clone()),
+            });
+            commands.entity(entity).insert(tag);
+        }
+
+        // rebuild every other copy with the new version
+        let mut others: HashMap<u64, Vec<(Entity, &BlueprintTag, &Transform, &EditorId)>> = default();
+        for item in q_instances.iter() {
+            if item.1.name == *name && !modules.contains(&item.0) {
+                others.entry(item.1.instance).or_default().push(item);
+            }
+        }
+        for (instance, members) in others {
+            // line it up with where the first module is (or would be)
+            let (_, tag, transform, _) = members
+                .iter()
+                .min_by_key(|(_, tag, ..)| tag.index)
+                .expect("theres at least one");
+            let anchor =
+                transform.translation -
+                blueprint.modules.get(tag.index).map_or(Vec3::ZERO, |m| m.offset);
+
+            // the new layout cant end up inside anything else, ignoring the copy thats being replaced
+            let ignore = members
+                .iter()
+                .flat_map(|(entity, ..)| q_children.iter_descendants(*entity))
+                .filter(|e| has_collider.get(*e).is_ok())
+                .collect::<Vec<_>>();
+            let predicate = |e| !ignore.contains(&e) && has_rigidbody.get(e).is_ok();
+            let filter = QueryFilter::only_fixed()
+                .exclude_sensors()
+                .predicate(&predicate);
+            let layout = blueprint.instantiate(name, instance, anchor);
+            let blocked = layout.iter().any(|module| {
+                let transform = Transform::from_translation(module.offset)
+                    .with_rotation(Quat::from_rotation_z(module.instructions.rotation));
+                let collider = module.instructions.body.collider();
+                rapier_ctx
+                    .intersection_with_shape_transform(transform, &collider, filter)
+                    .is_some()
+            });
+            if blocked {
+                warn!("a copy of blueprint {name} doesnt fit with the new version, detached it instead");
+                for (entity, tag, ..) in members {
+                    edits.push(Edit::Retag { entity, before: Some(tag.clone()), after: None });
+                    commands.entity(entity).remove::<BlueprintTag>();
+                }
+                continue;
+            }
+
+            let old_ids = members
+                .iter()
+                .map(|(_, tag, _, id)| (tag.index, **id))
+                .collect::<HashMap<_, _>>();
+            let before = members
+                .iter()
+                .filter_map(|(entity, _, _, id)| Some((**id, q_info.get(*entity)?)))
+                .collect();
+            for (entity, ..) in members {
+                selected.remove(entity);
+                commands.entity(entity).despawn_recursive();
+            }
+            // everything gets an id up front so undo can find them again
+            let after = layout
+                .into_iter()
+                .map(|module| {
+                    let index = module.blueprint.as_ref().map_or(0, |t| t.index);
+                    let id = old_ids.get(&index).copied().unwrap_or_else(|| ids.next());
+                    spawn_events.send(SpawnModule::new(module.clone()).with_id(id));
+                    (id, module)
+                })
+                .collect();
+            edits.push(Edit::Replace { before, after });
+        }
+
+        set_blueprint(&mut blueprints, name, Some(blueprint));
+        store_blueprint(&blueprints, name);
+        record.send(RecordEdit(Edit::Group(edits)));
+    }
+}
+
+/// take these modules out of whatever blueprint they were a copy of
+pub struct DetachBlueprint(pub Vec<Entity>);
+
+pub fn detach_blueprints(
+    mut commands: Commands,
+    mut events: EventReader<DetachBlueprint>,
+    q_tag: Query<&BlueprintTag>,
+    mut record: EventWriter<RecordEdit>,
+) {
+    for DetachBlueprint(modules) in events.iter() {
+        let mut edits = vec![];
+        for &entity in modules {
+            let Ok(tag) = q_tag.get(entity) else {
+                continue;
+            };
+            edits.push(Edit::Retag { entity, before: Some(tag.clone()), after: None });
+            commands.entity(entity).remove::<BlueprintTag>();
+        }
+        record.send(RecordEdit(Edit::Group(edits)));
+    }
+}
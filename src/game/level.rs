@@ -1,7 +1,19 @@
-use crate::engine::marble::MarbleType;
+use crate::engine::{
+    marble::{ Marble, MarbleType },
+    marble_io::FireMarbleEvent,
+    module_state::ModuleState,
+    sim::SimRng,
+    spawn::SpawnModule,
+    piece::{ PieceKind, SpawnPiece },
+};
+use crate::game::save_load::{ self, ModuleInfo, MachineQuery, SaveData, SaveSlots };
+use crate::interactive::history::History;
+use crate::modules::ModuleRegistry;
 use crate::*;
 
-use std::{ fs, cell::UnsafeCell };
+use std::{ fs, path::{ Path, PathBuf } };
+
+use serde::Serialize;
 
 use mlua::{ Function, Value, Table, ToLua, FromLua, prelude::{ LuaResult, LuaValue, LuaError } };
 
@@ -31,31 +43,207 @@ impl<'lua> FromLua<'lua> for MarbleType {
     }
 }
 
+impl<'lua> ToLua<'lua> for Marble {
+    fn to_lua(self, _: &'lua mlua::Lua) -> LuaResult<LuaValue<'lua>> {
+        Ok(match self.get_type() {
+            MarbleType::Bit => Value::Boolean(self.get_val() != 0),
+            MarbleType::Num => Value::Integer(self.get_val() as i64),
+        })
+    }
+}
+
+/// bools become bit marbles, numbers become num marbles, and nil becomes no marble at all
+fn marble_from_value(value: Value) -> LuaResult<Option<Marble>> {
+    match value {
+        Value::Nil => Ok(None),
+        Value::Boolean(b) => Ok(Some(Marble::bit(b))),
+        Value::Integer(n) => Ok(Some(Marble::new(MarbleType::Num, n as i32))),
+        Value::Number(n) => Ok(Some(Marble::new(MarbleType::Num, n.round() as i32))),
+        _ =>
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Marble",
+                message: Some("expected a boolean or a number".to_string()),
+            }),
+    }
+}
+
+impl<'lua> FromLua<'lua> for Marble {
+    fn from_lua(lua_value: Value<'lua>, _: &'lua mlua::Lua) -> LuaResult<Self> {
+        marble_from_value(lua_value)?.ok_or(LuaError::FromLuaConversionError {
+            from: "nil",
+            to: "Marble",
+            message: None,
+        })
+    }
+}
+
+/// the marbles going into every input (or coming out of every output) of a level in a single
+/// test case. when theres only one port its passed to lua as a plain value, otherwise as a table
+#[derive(Clone, Debug, Default, Deref, DerefMut, Serialize)]
+#[serde(transparent)]
+pub struct CaseMarbles(pub Vec<Option<Marble>>);
+
+impl CaseMarbles {
+    fn from_value(value: Value, n: usize) -> LuaResult<Self> {
+        if n == 1 {
+            return Ok(Self(vec![marble_from_value(value)?]));
+        }
+        let Value::Table(table) = value else {
+            return Err(LuaError::RuntimeError("expected a table of marbles".to_string()));
+        };
+        (1..=n)
+            .map(|i| marble_from_value(table.get(i)?))
+            .collect::<LuaResult<_>>()
+            .map(Self)
+    }
+}
+
+impl<'lua> ToLua<'lua> for CaseMarbles {
+    fn to_lua(self, lua: &'lua mlua::Lua) -> LuaResult<LuaValue<'lua>> {
+        if let [marble] = self.0[..] {
+            return marble.to_lua(lua);
+        }
+        let table = lua.create_table()?;
+        for (i, marble) in self.0.into_iter().enumerate() {
+            table.set(i + 1, marble)?;
+        }
+        Ok(Value::Table(table))
+    }
+}
+
+/// which port of the level a module is standing in for
+#[derive(Copy, Clone, Debug)]
+pub enum LevelPort {
+    Input(usize),
+    Output(usize),
+}
+
+impl LevelPort {
+    /// where this port sits in the world; inputs go along the left edge and outputs along the right
+    pub fn position(self, count: usize, grid_info: &grid::GridInfo) -> Vec3 {
+        let (index, side) = match self {
+            LevelPort::Input(i) => (i, -1.0),
+            LevelPort::Output(i) => (i, 1.0),
+        };
+        let spacing = grid_info.grid_size * 4.0;
+        let x = side * (grid_info.half_size - grid_info.grid_size * 2.0);
+        let y = ((count as f32 - 1.0) / 2.0 - index as f32) * spacing;
+        Vec3::new(x + 0.5, y + 0.5, 0.0)
+    }
+
+    /// the event to spawn the module standing in for this port
+    pub fn spawn_event(
+        self,
+        count: usize,
+        grid_info: &grid::GridInfo,
+        registry: &ModuleRegistry
+    ) -> SpawnModule {
+        let module = match self {
+            LevelPort::Input(_) => "input.level",
+            LevelPort::Output(_) => "output.level",
+        };
+        let module = registry.get(module).expect("level modules are built in");
+        let mut info = ModuleInfo::new(module);
+        info.offset = self.position(count, grid_info);
+        SpawnModule::new(info).port(self)
+    }
+}
+
 pub struct Level<'lua> {
-    name: String,
-    inputs: Vec<MarbleType>,
-    outputs: Vec<MarbleType>,
-    test: mlua::Function<'lua>,
-    generate: mlua::Function<'lua>,
+    /// the file name its loaded from, so it can be told apart from other levels with the same name
+    pub id: String,
+    /// where it was loaded from, so it can be loaded again somewhere that cant use our lua
+    pub path: PathBuf,
+    pub name: String,
+    pub description: String,
+    /// modules the player starts out with
+    pub modules: Vec<ModuleInfo>,
+    pub inputs: Vec<MarbleType>,
+    pub outputs: Vec<MarbleType>,
+    pub test: mlua::Function<'lua>,
+    pub generate: mlua::Function<'lua>,
 }
 
 impl<'lua> Level<'lua> {
     fn new(lua: &'lua mlua::Lua) -> Self {
         let func = lua.globals().get::<_, Function>("error").unwrap();
         Self {
+            id: String::new(),
+            path: PathBuf::new(),
             name: "Default".to_string(),
+            description: String::new(),
+            modules: vec![],
             inputs: vec![],
             outputs: vec![],
             test: func.clone(),
             generate: func,
         }
     }
+
+    /// run a level file and read back the `level` table it filled in, the modules it starts with
+    /// get looked up in `registry`
+    pub fn load(lua: &'lua mlua::Lua, path: &Path, registry: &ModuleRegistry) -> Result<Self, LocatedError> {
+        lua.set_app_data(registry.clone());
+        lua.globals().set("level", Level::new(lua))?;
+
+        let code = fs::read_to_string(path)?;
+        lua.load(&code).exec()?;
+
+        let mut level: Level = lua.globals().get("level")?;
+        level.id = path
+            .file_stem()
+            .map_or_else(|| level.name.clone(), |s| s.to_string_lossy().to_string());
+        level.path = path.to_path_buf();
+        Ok(level)
+    }
+
+    /// the inputs for test case `i` (starting from 1), or `None` once `generate` runs out
+    pub fn generate_case(&self, i: usize) -> LuaResult<Option<CaseMarbles>> {
+        let value: Value = self.generate.call(i)?;
+        if let Value::Nil = value {
+            return Ok(None);
+        }
+        CaseMarbles::from_value(value, self.inputs.len()).map(Some)
+    }
+
+    /// ask `test` whether these outputs are right for these inputs
+    pub fn test_case(&self, outputs: &CaseMarbles, inputs: &CaseMarbles) -> LuaResult<bool> {
+        self.test.call((outputs.clone(), inputs.clone()))
+    }
+
+    /// events to spawn the modules the player starts out with
+    pub fn starting_modules(&self) -> Vec<SpawnModule> {
+        self.modules
+            .iter()
+            .map(|module| SpawnModule::new(module.clone()))
+            .collect()
+    }
+
+    /// events to spawn a module for each of this level's inputs and outputs
+    pub fn port_modules(&self, grid_info: &grid::GridInfo, registry: &ModuleRegistry) -> Vec<SpawnModule> {
+        port_modules(self.inputs.len(), self.outputs.len(), grid_info, registry)
+    }
+}
+
+/// events to spawn a module for each of `inputs` level inputs and `outputs` level outputs
+pub fn port_modules(
+    inputs: usize,
+    outputs: usize,
+    grid_info: &grid::GridInfo,
+    registry: &ModuleRegistry
+) -> Vec<SpawnModule> {
+    (0..inputs)
+        .map(|i| LevelPort::Input(i).spawn_event(inputs, grid_info, registry))
+        .chain((0..outputs).map(|i| LevelPort::Output(i).spawn_event(outputs, grid_info, registry)))
+        .collect()
 }
 
 impl<'lua> ToLua<'lua> for Level<'lua> {
     fn to_lua(self, lua: &'lua mlua::Lua) -> LuaResult<LuaValue<'lua>> {
         let table = lua.create_table()?;
         table.set("name", self.name)?;
+        table.set("description", self.description)?;
         table.set("inputs", self.inputs)?;
         table.set("outputs", self.outputs)?;
         table.set("test", self.test)?;
@@ -68,12 +256,23 @@ impl<'lua> FromLua<'lua> for Level<'lua> {
     fn from_lua(lua_value: Value<'lua>, lua: &'lua mlua::Lua) -> LuaResult<Self> {
         let table: Table = Table::from_lua(lua_value, lua)?;
         let name: String = table.get("name")?;
+        let description: Option<String> = table.get("description")?;
+        let modules: Option<Vec<Table>> = table.get("modules")?;
+        let modules = modules
+            .unwrap_or_default()
+            .into_iter()
+            .map(|table| starting_module(lua, table))
+            .collect::<LuaResult<_>>()?;
         let inputs: Vec<MarbleType> = table.get("inputs")?;
         let outputs: Vec<MarbleType> = table.get("outputs")?;
         let test: Function = table.get("test")?;
         let generate: Function = table.get("generate")?;
         Ok(Level {
+            id: String::new(),
+            path: PathBuf::new(),
             name,
+            description: description.unwrap_or_default(),
+            modules,
             inputs,
             outputs,
             test,
@@ -82,6 +281,28 @@ impl<'lua> FromLua<'lua> for Level<'lua> {
     }
 }
 
+/// `{ type = "basic.module", x = 0, y = 0 }`, the old names like `"Basic"` work too. looked up in
+/// the registry [`Level::load`] left in the lua
+fn starting_module(lua: &mlua::Lua, table: Table) -> LuaResult<ModuleInfo> {
+    let ty: String = table.get("type")?;
+    let module = lua
+        .app_data_ref::<ModuleRegistry>()
+        .and_then(|registry| registry.get_by_name(&ty));
+    let Some(module) = module else {
+        return Err(LuaError::FromLuaConversionError {
+            from: "String",
+            to: "ModuleType",
+            message: Some(format!("no module called {ty}")),
+        });
+    };
+    let x: f32 = table.get("x")?;
+    let y: f32 = table.get("y")?;
+
+    let mut info = ModuleInfo::new(module);
+    info.offset = Vec3::new(x, y, 0.0);
+    Ok(info)
+}
+
 impl<'lua> std::ops::Deref for Levels<'lua> {
     type Target = Vec<Level<'lua>>;
     fn deref(&self) -> &Self::Target {
@@ -114,6 +335,16 @@ impl Drop for Lua {
     }
 }
 
+/// the folders levels get loaded from, module packs can add their own
+#[derive(Resource, Deref, DerefMut, Debug)]
+pub struct LevelDirs(pub Vec<PathBuf>);
+
+impl Default for LevelDirs {
+    fn default() -> Self {
+        LevelDirs(vec![PathBuf::from("data/levels")])
+    }
+}
+
 #[derive(Default, Resource)]
 pub struct Levels<'lua> {
     levels: Vec<Level<'lua>>,
@@ -125,38 +356,190 @@ pub fn load_levels(world: &mut World) {
 
     unsafe {
         let world = world as *mut World;
-        let result = load_levels_inner(world);
-        match result {
-            Ok(levels) => world.as_mut().expect("valid pointer").insert_non_send_resource(levels),
-            Err(err) => log_errors(In(Err(err))),
+        let levels = load_levels_inner(world);
+        world.as_mut().expect("valid pointer").insert_non_send_resource(levels);
+    }
+}
+
+unsafe fn load_levels_inner<'lua>(world: *mut World) -> Levels<'lua> {
+    let world = world.as_ref().expect("valid pointer");
+    let lua = world.get_non_send_resource::<Lua>().expect("Lua resource initialized");
+    let dirs = world.get_resource::<LevelDirs>().map(|dirs| dirs.0.clone()).unwrap_or_default();
+    let registry = world.get_resource::<ModuleRegistry>().cloned().unwrap_or_default();
+
+    Levels { levels: load_dirs(lua, &dirs, &registry) }
+}
+
+/// every level in these folders. a folder that isnt there or a level with a mistake in it gets
+/// logged and skipped, so one bad pack doesnt take the rest of the levels down with it
+pub fn load_dirs<'lua>(lua: &'lua mlua::Lua, dirs: &[PathBuf], registry: &ModuleRegistry) -> Vec<Level<'lua>> {
+    let mut levels = vec![];
+    for dir in dirs {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("couldnt read levels from {dir:?}: {e}");
+                continue;
+            }
+        };
+        let mut paths = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            // only lua files, anything else thats lying around gets left alone
+            .filter(|path| path.extension().map_or(false, |ext| ext == "lua"))
+            .collect::<Vec<_>>();
+        // so levels come out in the same order every time
+        paths.sort();
+
+        for path in paths {
+            match Level::load(lua, &path, registry) {
+                Ok(level) => levels.push(level),
+                Err(e) => error!("couldnt load level {path:?}: {e}"),
+            }
         }
     }
+    levels
 }
 
-unsafe fn load_levels_inner<'lua>(world: *mut World) -> Result<Levels<'lua>, LocatedError> {
-    let mut levels = Levels::default();
-    let lua = world
-        .as_ref()
-        .expect("valid pointer")
-        .get_non_send_resource::<Lua>()
-        .expect("Lua resource initialized");
+/// the index of the level that's currently loaded
+#[derive(Resource, Deref, DerefMut, Default)]
+pub struct CurrentLevel(pub Option<usize>);
 
-    // for every file in the levels directory
-    for path in fs::read_dir("data/levels")? {
-        let path = path?.path();
-        // if it's a lua file, load it and run it
-        if path.extension().unwrap() == "lua" {
-            lua.globals().set("level", Level::new(&*lua))?;
+/// load the level at this index, replacing everything in the world
+pub struct LoadLevel(pub usize);
 
-            let code = fs::read_to_string(path)?;
-            lua.load(&code).exec()?;
+/// fire the inputs of test case `n` of the current level
+pub struct RunCase(pub usize);
 
-            let level: Level = lua.globals().get("level")?;
-            levels.push(level);
+/// marbles that have reached each of the level's outputs
+#[derive(Resource, Deref, DerefMut, Default)]
+pub struct ReceivedMarbles(Vec<Vec<Marble>>);
+
+/// clear out the world and spawn in the ports of the level we're loading, along with whatever the
+/// player saved for it last (or the modules it starts with if theres nothing)
+#[allow(clippy::too_many_arguments)]
+pub fn load_level(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadLevel>,
+    levels: Option<NonSend<Levels<'static>>>,
+    q_modules: Query<Entity, Or<(With<marker::Module>, With<PieceKind>)>>,
+    q_machine: MachineQuery,
+    mut slots: ResMut<SaveSlots>,
+    mut spawn_events: EventWriter<SpawnModule>,
+    mut spawn_pieces: EventWriter<SpawnPiece>,
+    mut current: ResMut<CurrentLevel>,
+    mut received: ResMut<ReceivedMarbles>,
+    grid_info: Res<grid::GridInfo>,
+    mut rng: ResMut<SimRng>,
+    mut history: ResMut<History>,
+    registry: Res<ModuleRegistry>
+) {
+    let Some(&LoadLevel(index)) = load_events.iter().last() else {
+        return;
+    };
+    let Some(level) = levels.as_ref().and_then(|levels| levels.get(index)) else {
+        error!("There is no level #{index} to load");
+        return;
+    };
+
+    // dont lose whatever was being worked on
+    if slots.level.is_some() {
+        let save = SaveData::from(q_machine.snapshot());
+        if let Err(e) = slots.write(save_load::AUTOSAVE, &save) {
+            error!("Failed to autosave: {e}");
         }
     }
 
-    Ok(levels)
+    for module in q_modules.iter() {
+        commands.entity(module).despawn_recursive();
+    }
+    for event in level.port_modules(&grid_info, &registry) {
+        spawn_events.send(event);
+    }
+
+    slots.level = Some(level.id.clone());
+    let restored = slots.latest().and_then(|name| {
+        slots
+            .read(&name, &registry)
+            .map_err(|e| error!("Failed to restore {name}: {e}"))
+            .ok()
+    });
+    match restored {
+        Some(save) => {
+            for module in save.modules {
+                spawn_events.send(SpawnModule::new(module));
+            }
+            for piece in save.pieces {
+                spawn_pieces.send(SpawnPiece::new(piece));
+            }
+        }
+        None => {
+            for event in level.starting_modules() {
+                spawn_events.send(event);
+            }
+        }
+    }
+
+    **current = Some(index);
+    rng.reset();
+    // nothing from the last level is worth undoing
+    history.clear();
+    received.clear();
+}
+
+/// fire the marbles `generate` gives us for this case out of the level inputs
+pub fn fire_level_inputs(
+    mut run_events: EventReader<RunCase>,
+    levels: Option<NonSend<Levels<'static>>>,
+    current: Res<CurrentLevel>,
+    q_inputs: Query<(&marker::LevelInput, &ModuleState)>,
+    mut fire_events: EventWriter<FireMarbleEvent>,
+    mut received: ResMut<ReceivedMarbles>,
+    mut rng: ResMut<SimRng>
+) {
+    let Some(&RunCase(case)) = run_events.iter().last() else {
+        return;
+    };
+    let Some(level) = current.and_then(|i| levels.as_ref()?.get(i)) else {
+        return;
+    };
+
+    let marbles = match level.generate_case(case) {
+        Ok(Some(marbles)) => marbles,
+        Ok(None) => {
+            info!("Level \"{}\" has no case #{case}", level.name);
+            return;
+        }
+        Err(err) => {
+            error!("{err}");
+            return;
+        }
+    };
+
+    received.clear();
+    // so running the same case twice does the same thing twice
+    rng.reset();
+    for (&marker::LevelInput(n), state) in q_inputs.iter() {
+        if let Some(&Some(marble)) = marbles.get(n) {
+            fire_events.send(FireMarbleEvent::new(marble, state.outputs[0], 1.0));
+        }
+    }
+}
+
+/// take any marble sitting in a level output and record it
+pub fn collect_outputs(
+    mut q_outputs: Query<(&marker::LevelOutput, &mut ModuleState)>,
+    mut received: ResMut<ReceivedMarbles>
+) {
+    for (&marker::LevelOutput(n), mut state) in q_outputs.iter_mut() {
+        if state.input_state[0].is_some() {
+            let marble = state.input_state[0].take().unwrap();
+            if received.len() <= n {
+                received.resize(n + 1, vec![]);
+            }
+            received[n].push(marble);
+        }
+    }
 }
 
 #[test]
@@ -177,8 +560,10 @@ fn test_lua() -> Result<(), LuaError> {
     let level: Level = lua.globals().get("level")?;
 
     assert_eq!(level.name, "Start");
-    let ret: bool = level.test.call(true)?;
+    let ret: bool = level.test.call((true, true))?;
     assert_eq!(ret, true);
+    let ret: bool = level.test.call((false, true))?;
+    assert_eq!(ret, false);
 
     Ok(())
 }
\ No newline at end of file
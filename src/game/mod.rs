@@ -1,16 +1,60 @@
 pub mod save_load;
+/// the save file format and migrating old saves to it
+pub mod save_file;
+pub mod blueprint;
 pub mod level;
+pub mod progress;
+/// running levels against machines without a window
+pub mod verify;
+/// the simulation on its own, without anything to draw it
+pub mod simulation;
 
 use crate::*;
+use iyes_loopless::prelude::*;
+
+/// which screen the player is looking at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    LevelSelect,
+    Playing,
+}
 
 pub fn app(app: &mut App) {
-    app.add_event::<save_load::SaveWorld>()
+    app.add_loopless_state(GameState::LevelSelect)
+        .add_event::<save_load::SaveWorld>()
         .add_event::<save_load::LoadWorld>()
+        .add_event::<level::LoadLevel>()
+        .add_event::<level::RunCase>()
+        .add_event::<blueprint::SpawnBlueprint>()
+        .add_event::<blueprint::SaveBlueprint>()
+        .add_event::<blueprint::DetachBlueprint>()
+        .init_resource::<blueprint::Blueprints>()
+        .init_resource::<level::CurrentLevel>()
+        .init_resource::<save_load::SaveSlots>()
         .add_system(save_load::save_world)
+        .add_system(save_load::autosave.run_in_state(GameState::Playing))
+        .add_system_to_stage(CoreStage::Last, save_load::autosave_on_exit)
+        .add_system(blueprint::detach_blueprints)
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            blueprint::spawn_blueprints.before("spawn::spawn_modules")
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            blueprint::save_blueprints.before("spawn::spawn_modules")
+        )
         .add_system_to_stage(
             CoreStage::PreUpdate,
             save_load::load_world.before("spawn::spawn_modules")
         )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            level::load_level.before("spawn::spawn_modules")
+        )
+        .add_system_to_stage(CoreStage::PreUpdate, level::fire_level_inputs)
         .init_non_send_resource::<level::Lua>()
-        .add_startup_system(level::load_levels);
+        .init_resource::<level::LevelDirs>()
+        .add_startup_system(level::load_levels)
+        .add_startup_system(blueprint::load_blueprints)
+        .add_startup_system(progress::load_progress);
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+use serde::{ Serialize, Deserialize };
+
+use crate::*;
+
+/// where the players progress gets written
+pub const PROGRESS_PATH: &str = "data/progress.ron";
+
+/// how the player has done on a level
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LevelProgress {
+    pub completed: bool,
+    /// the fewest ticks every case took put together
+    #[serde(default)]
+    pub best_ticks: Option<usize>,
+    /// the fewest modules they did it with
+    #[serde(default)]
+    pub best_modules: Option<usize>,
+}
+
+/// progress on every level, by [`Level::id`](super::level::Level)
+#[derive(Resource, Serialize, Deserialize, Debug, Default, Deref, DerefMut)]
+pub struct Progress(BTreeMap<String, LevelProgress>);
+
+impl Progress {
+    /// mark a level as completed, keeping whichever scores are better
+    pub fn complete(&mut self, level: &str, ticks: usize, modules: usize) {
+        let progress = self.entry(level.to_string()).or_default();
+        progress.completed = true;
+        progress.best_ticks = Some(progress.best_ticks.map_or(ticks, |t| t.min(ticks)));
+        progress.best_modules = Some(progress.best_modules.map_or(modules, |m| m.min(modules)));
+    }
+
+    pub fn completed(&self, level: &str) -> bool {
+        self.get(level).map_or(false, |p| p.completed)
+    }
+
+    pub fn write(&self) -> Result<(), LocatedError> {
+        let serialized = ron::ser::to_string(self)?;
+        std::fs::create_dir_all("data")?;
+        std::fs::write(PROGRESS_PATH, serialized)?;
+        Ok(())
+    }
+}
+
+fn read_progress() -> Result<Progress, LocatedError> {
+    let serialized = std::fs::read_to_string(PROGRESS_PATH)?;
+    Ok(ron::de::from_str(&serialized)?)
+}
+
+/// read the progress file on startup, no file just means a fresh start
+pub fn load_progress(mut commands: Commands) {
+    let progress = match read_progress() {
+        Ok(progress) => progress,
+        Err(e) => {
+            if std::path::Path::new(PROGRESS_PATH).exists() {
+                error!("couldnt read {PROGRESS_PATH}: {e}");
+            }
+            default()
+        }
+    };
+    commands.insert_resource(progress);
+}
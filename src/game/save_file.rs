@@ -0,0 +1,577 @@
+use std::{ cell::RefCell, fmt };
+
+use serde::{ Serialize, Deserialize, Deserializer, de::{ DeserializeOwned, IgnoredAny } };
+
+use crate::{
+    *,
+    modules::{ ModuleRegistry, Module, registry::Name },
+    engine::piece::PieceInfo,
+    game::{ save_load::{ ModuleInfo, SaveData }, blueprint::{ Blueprint, BlueprintTag, PortRef } },
+};
+
+/// the version of the save format we write, bump it and add a migration whenever it changes
+pub const SAVE_VERSION: u32 = 2;
+
+/// whats at the top of every save file
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SaveHeader {
+    pub version: u32,
+    /// the id of the level it was saved for
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub name: String,
+    /// seconds since the unix epoch
+    #[serde(default)]
+    pub saved_at: u64,
+}
+
+impl SaveHeader {
+    /// a header for something being written right now
+    pub fn new(level: Option<&str>, name: &str) -> Self {
+        let saved_at = std::time::SystemTime
+            ::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        SaveHeader {
+            version: SAVE_VERSION,
+            level: level.map(str::to_string),
+            name: name.to_string(),
+            saved_at,
+        }
+    }
+}
+
+/// a module as its written to a save, only the stuff the player can actually change so changes to
+/// how modules are defined dont break old saves
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedModule {
+    /// the identifier of its [`modules::ModuleType`]
+    pub ty: String,
+    pub offset: Vec3,
+    /// of the body
+    #[serde(default)]
+    pub rotation: f32,
+    /// (rotation, ext) of every input
+    #[serde(default)]
+    pub inputs: Vec<(f32, f32)>,
+    /// (rotation, ext) of every output
+    #[serde(default)]
+    pub outputs: Vec<(f32, f32)>,
+    /// its config, as its own bit of ron so a bad one doesnt take the rest of the save with it
+    #[serde(default)]
+    pub config: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blueprint: Option<BlueprintTag>,
+}
+
+impl From<&ModuleInfo> for SavedModule {
+    fn from(info: &ModuleInfo) -> Self {
+        let ports = |ports: &[modules::Instruction]| {
+            ports
+                .iter()
+                .map(|p| (p.rotation, p.ext))
+                .collect()
+        };
+        SavedModule {
+            ty: info.module_type.get_identifier().to_string(),
+            offset: info.offset,
+            rotation: info.instructions.rotation,
+            inputs: ports(&info.instructions.inputs),
+            outputs: ports(&info.instructions.outputs),
+            config: ron::ser::to_string(&info.module).unwrap_or_default(),
+            blueprint: info.blueprint.clone(),
+        }
+    }
+}
+
+impl SavedModule {
+    /// turn it back into something that can be spawned, anything thats wrong but not fatal gets
+    /// reset to the default and written down in `warnings`
+    pub fn to_info(
+        &self,
+        registry: &ModuleRegistry,
+        warnings: &mut Vec<String>
+    ) -> Result<ModuleInfo, String> {
+        let Some(ty) = registry.get(&self.ty) else {
+            return Err(format!("unknown module type \"{}\"", self.ty));
+        };
+        let name = ty.get_name().to_string();
+        let mut info = ModuleInfo::new(ty);
+        info.offset = self.offset;
+        info.instructions.rotation = self.rotation;
+        info.blueprint = self.blueprint.clone();
+
+        let mut set_ports = |ports: &mut Vec<modules::Instruction>, saved: &[(f32, f32)], what| {
+            if ports.len() != saved.len() {
+                warnings.push(format!("{name} has a different number of {what} now, reset them"));
+                return;
+            }
+            for (port, &(rotation, ext)) in ports.iter_mut().zip(saved) {
+                port.rotation = rotation;
+                port.ext = ext;
+            }
+        };
+        set_ports(&mut info.instructions.inputs, &self.inputs, "inputs");
+        set_ports(&mut info.instructions.outputs, &self.outputs, "outputs");
+
+        if !self.config.is_empty() {
+            match registry.scope(|| ron::de::from_str::<Box<dyn Module>>(&self.config)) {
+                Ok(module) => info.module = module,
+                Err(e) => warnings.push(format!("couldnt read the config of {name}, reset it: {e}")),
+            }
+        }
+        Ok(info)
+    }
+}
+
+/// a save file, as of [`SAVE_VERSION`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveFile {
+    pub header: SaveHeader,
+    pub modules: Vec<SavedModule>,
+    #[serde(default)]
+    pub pieces: Vec<PieceInfo>,
+}
+
+impl SaveFile {
+    pub fn new(save: &SaveData, level: Option<&str>, name: &str) -> Self {
+        SaveFile {
+            header: SaveHeader::new(level, name),
+            modules: save.modules.iter().map(SavedModule::from).collect(),
+            pieces: save.pieces.clone(),
+        }
+    }
+}
+
+/// a blueprint file, versioned along with saves so they get migrated the same way
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlueprintFile {
+    pub header: SaveHeader,
+    pub modules: Vec<SavedModule>,
+    #[serde(default)]
+    pub inputs: Vec<PortRef>,
+    #[serde(default)]
+    pub outputs: Vec<PortRef>,
+}
+
+impl BlueprintFile {
+    pub fn new(blueprint: &Blueprint, name: &str) -> Self {
+        BlueprintFile {
+            header: SaveHeader::new(None, name),
+            modules: blueprint.modules.iter().map(SavedModule::from).collect(),
+            inputs: blueprint.inputs.clone(),
+            outputs: blueprint.outputs.clone(),
+        }
+    }
+}
+
+/// a port as v0 and v1 saves had it
+#[derive(Deserialize)]
+struct LegacyPort {
+    ext: f32,
+    rotation: f32,
+}
+
+/// the `SpawnInstructions` of a module as v0 and v1 saves had them, the body comes from the type
+/// so its left out
+#[derive(Deserialize)]
+struct LegacyInstructions {
+    #[serde(default)]
+    rotation: f32,
+    inputs: Vec<LegacyPort>,
+    outputs: Vec<LegacyPort>,
+}
+
+#[derive(Deserialize)]
+struct LegacyBlueprintTag {
+    name: String,
+    instance: u64,
+    index: usize,
+}
+
+/// how far along reading the configs of a legacy save is, see [`from_legacy`]
+#[derive(Default)]
+struct LegacyConfigs {
+    next: usize,
+    /// the ones that didnt work last time, these get left alone
+    skip: Vec<usize>,
+    failed: Option<usize>,
+}
+
+thread_local! {
+    /// the legacy save being read on this thread, see [`from_legacy`]
+    static LEGACY_CONFIGS: RefCell<LegacyConfigs> = RefCell::new(LegacyConfigs::default());
+}
+
+/// the config of a module in a v0 or v1 save, read with todays modules. `None` if todays modules
+/// couldnt make sense of it
+struct LegacyConfig(Option<Box<dyn Module>>);
+
+impl<'de> Deserialize<'de> for LegacyConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (index, skip) = LEGACY_CONFIGS.with(|configs| {
+            let mut configs = configs.borrow_mut();
+            configs.next += 1;
+            (configs.next - 1, configs.skip.contains(&(configs.next - 1)))
+        });
+        if skip {
+            IgnoredAny::deserialize(deserializer)?;
+            return Ok(LegacyConfig(None));
+        }
+        match <Box<dyn Module>>::deserialize(deserializer) {
+            Ok(module) => Ok(LegacyConfig(Some(module))),
+            Err(e) => {
+                LEGACY_CONFIGS.with(|configs| configs.borrow_mut().failed = Some(index));
+                Err(e)
+            }
+        }
+    }
+}
+
+/// read a v0 or v1 save or blueprint. ron cant pick up where it left off after a config fails, so
+/// every time one does its read again without that config until the rest of it works
+fn from_legacy<T: DeserializeOwned>(
+    text: &str,
+    registry: &ModuleRegistry
+) -> Result<T, ron::error::SpannedError> {
+    let mut skip = vec![];
+    loop {
+        LEGACY_CONFIGS.with(|configs| {
+            *configs.borrow_mut() = LegacyConfigs { skip: skip.clone(), ..default() };
+        });
+        let result = registry.scope(|| ron::de::from_str::<T>(text));
+        let failed = LEGACY_CONFIGS.with(|configs| configs.borrow_mut().failed.take());
+        match (result, failed) {
+            (Err(_), Some(failed)) => skip.push(failed),
+            (result, _) => {
+                return result;
+            }
+        }
+    }
+}
+
+/// a module as v0 and v1 saves had it, which was `ModuleInfo` as it was back then. its written
+/// out here so `ModuleInfo` can change without breaking them. the config is the only part still
+/// read with todays modules, same as [`SavedModule::config`]
+#[derive(Deserialize)]
+struct LegacyModule {
+    instructions: LegacyInstructions,
+    module: LegacyConfig,
+    /// the name of its `ModuleType` variant
+    module_type: Name,
+    offset: Vec3,
+    #[serde(default)]
+    blueprint: Option<LegacyBlueprintTag>,
+}
+
+impl LegacyModule {
+    fn into_saved(self, registry: &ModuleRegistry, warnings: &mut Vec<String>) -> SavedModule {
+        let Name(name) = self.module_type;
+        let ports = |ports: &[LegacyPort]| {
+            ports
+                .iter()
+                .map(|p| (p.rotation, p.ext))
+                .collect()
+        };
+        let ty = registry.get_by_name(&name);
+        let config = match self.module.0 {
+            Some(module) => ron::ser::to_string(&module).unwrap_or_default(),
+            None => {
+                // if the type is gone too it gets skipped, thats enough to say about it
+                if let Some(ty) = &ty {
+                    warnings.push(format!("couldnt read the config of {}, reset it", ty.get_name()));
+                }
+                String::new()
+            }
+        };
+        SavedModule {
+            // if its not around anymore it gets skipped when its loaded
+            ty: ty.map_or(name, |ty| ty.get_identifier().to_string()),
+            offset: self.offset,
+            rotation: self.instructions.rotation,
+            inputs: ports(&self.instructions.inputs),
+            outputs: ports(&self.instructions.outputs),
+            config,
+            blueprint: self.blueprint.map(|LegacyBlueprintTag { name, instance, index }| {
+                BlueprintTag { name, instance, index }
+            }),
+        }
+    }
+}
+
+/// version 0, just a list of modules
+type SaveV0 = Vec<LegacyModule>;
+
+/// version 1, modules and pieces
+#[derive(Deserialize)]
+struct SaveV1 {
+    modules: Vec<LegacyModule>,
+    #[serde(default)]
+    pieces: Vec<PieceInfo>,
+}
+
+/// a [`PortRef`] as v1 blueprints had it
+#[derive(Deserialize)]
+struct LegacyPortRef {
+    module: usize,
+    port: usize,
+}
+
+impl From<LegacyPortRef> for PortRef {
+    fn from(LegacyPortRef { module, port }: LegacyPortRef) -> Self {
+        PortRef { module, port }
+    }
+}
+
+/// version 1 blueprints, written before they had a header
+#[derive(Deserialize)]
+struct BlueprintV1 {
+    modules: Vec<LegacyModule>,
+    inputs: Vec<LegacyPortRef>,
+    outputs: Vec<LegacyPortRef>,
+}
+
+fn migrate_blueprint_v1(
+    blueprint: BlueprintV1,
+    registry: &ModuleRegistry,
+    warnings: &mut Vec<String>
+) -> BlueprintFile {
+    BlueprintFile {
+        header: SaveHeader { version: 2, ..default() },
+        modules: blueprint.modules
+            .into_iter()
+            .map(|module| module.into_saved(registry, warnings))
+            .collect(),
+        inputs: blueprint.inputs.into_iter().map(PortRef::from).collect(),
+        outputs: blueprint.outputs.into_iter().map(PortRef::from).collect(),
+    }
+}
+
+fn migrate_v0(save: SaveV0) -> SaveV1 {
+    SaveV1 { modules: save, pieces: vec![] }
+}
+
+fn migrate_v1(save: SaveV1, registry: &ModuleRegistry, warnings: &mut Vec<String>) -> SaveFile {
+    SaveFile {
+        header: SaveHeader { version: 2, ..default() },
+        modules: save.modules
+            .into_iter()
+            .map(|module| module.into_saved(registry, warnings))
+            .collect(),
+        pieces: save.pieces,
+    }
+}
+
+/// how loading a save went, modules that couldnt be loaded get left out instead of the whole
+/// thing failing
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct LoadReport {
+    /// the version the file was written with
+    pub version: u32,
+    pub skipped: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl LoadReport {
+    /// nothing went wrong and it didnt need migrating
+    pub fn is_clean(&self) -> bool {
+        self.version == SAVE_VERSION && self.skipped.is_empty() && self.warnings.is_empty()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "save format v{}", self.version)?;
+        if self.version != SAVE_VERSION {
+            write!(f, " (migrated to v{SAVE_VERSION})")?;
+        }
+        for skipped in self.skipped.iter() {
+            write!(f, "\n  skipped: {skipped}")?;
+        }
+        for warning in self.warnings.iter() {
+            write!(f, "\n  warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// a save that couldnt be read at all
+#[derive(Debug)]
+pub struct SaveError(pub String);
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// a save thats been read, migrated and validated
+#[derive(Debug, Clone)]
+pub struct LoadedSave {
+    pub header: SaveHeader,
+    pub data: SaveData,
+    pub report: LoadReport,
+}
+
+/// read a save of any version, bringing it up to date and checking every module in it against
+/// `registry`
+pub fn parse(text: &str, registry: &ModuleRegistry) -> Result<LoadedSave, SaveError> {
+    let mut warnings = vec![];
+    let (file, version) = match ron::de::from_str::<SaveFile>(text) {
+        Ok(file) => {
+            let version = file.header.version;
+            (file, version)
+        }
+        Err(current) => {
+            let older = from_legacy::<SaveV1>(text, registry)
+                .map(|save| (save, 1))
+                .or_else(|_| from_legacy::<SaveV0>(text, registry).map(|save| (migrate_v0(save), 0)));
+            match older {
+                Ok((save, version)) => (migrate_v1(save, registry, &mut warnings), version),
+                // its probably a current save thats broken, so thats the error that matters
+                Err(_) => {
+                    return Err(SaveError(format!("not a save file: {current}")));
+                }
+            }
+        }
+    };
+    check_version(version)?;
+
+    let mut report = LoadReport { version, warnings, ..default() };
+    let modules = to_infos(&file.modules, registry, &mut report);
+
+    Ok(LoadedSave {
+        header: file.header,
+        data: SaveData { modules, pieces: file.pieces },
+        report,
+    })
+}
+
+/// read a blueprint of any version, like [`parse`]. if a module gets skipped the ports pointing at
+/// it or anything after it would be off, so that fails the whole blueprint instead
+pub fn parse_blueprint(
+    text: &str,
+    registry: &ModuleRegistry
+) -> Result<(Blueprint, LoadReport), SaveError> {
+    let mut warnings = vec![];
+    let (file, version) = match ron::de::from_str::<BlueprintFile>(text) {
+        Ok(file) => {
+            let version = file.header.version;
+            (file, version)
+        }
+        Err(current) => {
+            match from_legacy::<BlueprintV1>(text, registry) {
+                Ok(blueprint) => (migrate_blueprint_v1(blueprint, registry, &mut warnings), 1),
+                Err(_) => {
+                    return Err(SaveError(format!("not a blueprint: {current}")));
+                }
+            }
+        }
+    };
+    check_version(version)?;
+
+    let mut report = LoadReport { version, warnings, ..default() };
+    let modules = to_infos(&file.modules, registry, &mut report);
+    if !report.skipped.is_empty() {
+        return Err(SaveError(format!("couldnt load every module in it: {report}")));
+    }
+    let blueprint = Blueprint { modules, inputs: file.inputs, outputs: file.outputs };
+    Ok((blueprint, report))
+}
+
+fn check_version(version: u32) -> Result<(), SaveError> {
+    if version > SAVE_VERSION {
+        return Err(
+            SaveError(format!("save is v{version} but this version of the game only reads up to v{SAVE_VERSION}"))
+        );
+    }
+    Ok(())
+}
+
+/// check every module, leaving out the ones that cant be loaded
+fn to_infos(
+    modules: &[SavedModule],
+    registry: &ModuleRegistry,
+    report: &mut LoadReport
+) -> Vec<ModuleInfo> {
+    modules
+        .iter()
+        .enumerate()
+        .filter_map(|(i, module)| {
+            module
+                .to_info(registry, &mut report.warnings)
+                .map_err(|e| report.skipped.push(format!("module #{}: {e}", i + 1)))
+                .ok()
+        })
+        .collect()
+}
+
+/// a save from before there was a header, written by the game as it was back then
+#[cfg(test)]
+const SAVE_V0: &str = concat!(
+    "[(instructions:(body:Small,inputs:[(offset:(0.0,0.0,0.0),ext:8.5,rotation:-3.1415927)],",
+    "outputs:[(offset:(0.0,0.0,0.0),ext:8.0,rotation:0.0)]),module:{\"type\":\"Basic\"},",
+    "module_type:Basic,offset:(-48.0,16.0,0.0))]"
+);
+
+#[test]
+fn test_migrate() {
+    let registry = ModuleRegistry::with_builtin();
+
+    let loaded = parse(SAVE_V0, &registry).unwrap();
+    assert_eq!(loaded.report.version, 0);
+    assert_eq!(loaded.data.modules.len(), 1);
+    let module = &loaded.data.modules[0];
+    assert_eq!(module.module_type.get_identifier(), "basic.module");
+    assert_eq!(module.offset, Vec3::new(-48.0, 16.0, 0.0));
+    assert!(loaded.report.warnings.is_empty());
+
+    let save = SaveData { modules: vec![module.clone()], pieces: vec![] };
+
+    // modules nobody knows about get skipped instead of failing the whole thing
+    let mut file = SaveFile::new(&save, None, "test");
+    file.modules.push(SavedModule { ty: "nonexistent".to_string(), ..file.modules[0].clone() });
+    let loaded = parse(&ron::ser::to_string(&file).unwrap(), &registry).unwrap();
+    assert_eq!(loaded.data.modules.len(), 1);
+    assert_eq!(loaded.report.skipped.len(), 1);
+}
+
+#[test]
+fn test_migrate_unknown() {
+    let registry = ModuleRegistry::with_builtin();
+
+    // a module thats not around anymore and one whose config doesnt fit it now, neither of them
+    // takes the rest of the save down with it
+    let module = SAVE_V0.trim_start_matches('[').trim_end_matches(']');
+    let gone = module
+        .replace("{\"type\":\"Basic\"}", "{\"type\":\"Gone\",\"speed\":3}")
+        .replace("module_type:Basic", "module_type:Gone");
+    let changed = module.replace("{\"type\":\"Basic\"}", "{\"type\":\"Basic\",\"delay\":\"slow\"}");
+    let save = format!("(modules:[{gone},{module},{changed}],pieces:[])");
+
+    let loaded = parse(&save, &registry).unwrap();
+    assert_eq!(loaded.report.version, 1);
+    assert_eq!(loaded.data.modules.len(), 2);
+    assert_eq!(loaded.report.skipped.len(), 1);
+    assert_eq!(loaded.report.warnings.len(), 1);
+}
+
+#[test]
+fn test_blueprint() {
+    let registry = ModuleRegistry::with_builtin();
+
+    // blueprints from before they had a header are the same modules as a v0 save
+    let old = format!("(modules:{SAVE_V0},inputs:[(module:0,port:0)],outputs:[])");
+    let (blueprint, report) = parse_blueprint(&old, &registry).unwrap();
+    assert_eq!(report.version, 1);
+    assert_eq!(blueprint.modules.len(), 1);
+    assert_eq!(blueprint.inputs, vec![PortRef { module: 0, port: 0 }]);
+
+    let written = ron::ser::to_string(&BlueprintFile::new(&blueprint, "test")).unwrap();
+    let (loaded, report) = parse_blueprint(&written, &registry).unwrap();
+    assert!(report.is_clean());
+    assert_eq!(loaded.modules.len(), 1);
+}
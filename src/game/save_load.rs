@@ -1,22 +1,113 @@
-use std::{ fs::File, io::Write };
+use std::path::{ Path, PathBuf };
 
-use bevy::tasks::IoTaskPool;
+use bevy::{ app::AppExit, ecs::system::SystemParam, tasks::IoTaskPool, window::WindowCloseRequested };
+use iyes_loopless::prelude::*;
 use serde::{ Serialize, Deserialize };
 
 use crate::{
     *,
-    modules::{ ModuleType, SpawnInstructions, ModuleComponent, Module },
-    engine::{module_state::ModuleState, spawn::SpawnModule},
+    modules::{ ModuleType, ModuleRegistry, SpawnInstructions, ModuleComponent, Module, Instruction },
+    engine::{
+        module_state::ModuleState,
+        spawn::{ SpawnModule, EditorId, EditorIds },
+        piece::{ PieceInfo, PieceKind, SpawnPiece },
+        sim::SimRng,
+    },
+    interactive::history::{ RecordEdit, Edit },
+    game::{ GameState, blueprint::BlueprintTag, save_file::{ self, SaveFile, LoadedSave } },
 };
 
+/// where every levels saves go, each level gets a folder of its own
+pub const SAVE_DIR: &str = "data/saves";
+/// what autosaves are called
+pub const AUTOSAVE: &str = "autosave";
+/// how often to autosave
+const AUTOSAVE_SECS: f32 = 60.0;
+
+/// save the machine under this name for the current level
 pub struct SaveWorld(pub String);
 
-#[derive(Serialize, Deserialize, Debug)]
+/// where saves go for the level being played
+#[derive(Resource, Debug)]
+pub struct SaveSlots {
+    /// the folder every levels folder goes in, [`SAVE_DIR`] unless its changed
+    pub root: PathBuf,
+    /// the id of the level, `None` until one gets loaded
+    pub level: Option<String>,
+    /// what went wrong the last time a save was loaded, if anything did
+    pub report: Option<String>,
+}
+
+impl Default for SaveSlots {
+    fn default() -> Self {
+        Self { root: PathBuf::from(SAVE_DIR), level: None, report: None }
+    }
+}
+
+impl SaveSlots {
+    pub fn dir(&self) -> PathBuf {
+        self.root.join(self.level.as_deref().unwrap_or("sandbox"))
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir().join(format!("{name}.ron"))
+    }
+
+    /// the names of every save for this level, most recent first
+    pub fn list(&self) -> Vec<String> {
+        let Ok(dir) = std::fs::read_dir(self.dir()) else {
+            return vec![];
+        };
+        let mut saves = dir
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "ron"))
+            .filter_map(|path| {
+                let modified = path.metadata().and_then(|m| m.modified()).ok()?;
+                Some((modified, path.file_stem()?.to_str()?.to_string()))
+            })
+            .collect::<Vec<_>>();
+        saves.sort_by(|a, b| b.0.cmp(&a.0));
+        saves.into_iter().map(|(_, name)| name).collect()
+    }
+
+    /// whatever was saved last, the autosave usually
+    pub fn latest(&self) -> Option<String> {
+        self.list().into_iter().next()
+    }
+
+    /// read one of this levels saves, keeping track of anything that went wrong
+    pub fn read(&mut self, name: &str, registry: &ModuleRegistry) -> Result<SaveData, LocatedError> {
+        let loaded = read_save(self.path(name), registry).map_err(|e| {
+            self.report = Some(format!("couldnt load {name}: {e}"));
+            e
+        })?;
+        self.report = None;
+        if !loaded.report.is_clean() {
+            warn!("loaded {name}: {}", loaded.report);
+            self.report = Some(format!("loaded {name}: {}", loaded.report));
+        }
+        if loaded.header.level.is_some() && loaded.header.level != self.level {
+            warn!("{name} was saved for a different level ({:?})", loaded.header.level);
+        }
+        Ok(loaded.data)
+    }
+
+    /// write the machine to one of this levels saves
+    pub fn write(&self, name: &str, save: &SaveData) -> Result<(), LocatedError> {
+        write_save(self.path(name), &SaveFile::new(save, self.level.as_deref(), name))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ModuleInfo {
     pub instructions: SpawnInstructions,
     pub module: Box<dyn Module>,
     pub module_type: ModuleType,
     pub offset: Vec3,
+    /// the blueprint this is a copy of a part of, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blueprint: Option<BlueprintTag>,
 }
 
 impl ModuleInfo {
@@ -26,90 +117,274 @@ impl ModuleInfo {
             instructions: module.spawn_instructions().clone(),
             module_type: module,
             offset: Vec3::ZERO,
+            blueprint: None,
         }
     }
 }
 
-pub fn save_world(
-    q_modules: Query<Entity, With<marker::Module>>,
-    q_module: Query<&ModuleComponent>,
-    q_state: Query<&ModuleState>,
-    mut save_events: EventReader<SaveWorld>,
-    q_transform: Query<&Transform>
-) {
-    let Some(SaveWorld(path)) = save_events.iter().next() else {
-        return;
-    };
-    let path = path.clone();
-
-    let mut instructions: Vec<ModuleInfo> = vec![];
+/// everything needed to turn a module entity back into a [`ModuleInfo`]
+#[derive(SystemParam)]
+pub struct ModuleInfoQuery<'w, 's> {
+    q_module: Query<'w, 's, &'static ModuleComponent>,
+    q_state: Query<'w, 's, &'static ModuleState>,
+    q_transform: Query<'w, 's, &'static Transform>,
+    q_blueprint: Query<'w, 's, &'static BlueprintTag>,
+}
 
-    for module in q_modules.iter() {
-        let component = q_module.get(module).unwrap();
-        let state = q_state.get(module).unwrap();
+impl<'w, 's> ModuleInfoQuery<'w, 's> {
+    /// a snapshot of this module as it is right now, enough to spawn it back in exactly the same
+    pub fn get(&self, module: Entity) -> Option<ModuleInfo> {
+        let q_transform = &self.q_transform;
+        let component = self.q_module.get(module).ok()?;
+        let state = self.q_state.get(module).ok()?;
         let mut instruction = component.ty.spawn_instructions().clone();
+        let body = instruction.body;
+        let body_rot = q_transform.get(state.body).ok()?.rotation.to_euler(EulerRot::XYZ).2;
+        instruction.rotation = body_rot;
 
-        for (i, input) in state.inputs.iter().enumerate() {
-            let rot = q_transform.get(*input).unwrap().rotation;
-            instruction.inputs[i].rotation = rot.to_euler(EulerRot::XYZ).2;
+        // ports sit on the edge of the body so move them in or out with it
+        let save_port = |port: &mut Instruction, entity: &Entity| {
+            let Ok(transform) = q_transform.get(*entity) else {
+                return;
+            };
+            let rot = transform.rotation.to_euler(EulerRot::XYZ).2;
+            port.ext += body.offset_at(rot - body_rot) - body.offset_at(port.rotation);
+            port.rotation = rot;
+        };
+        for (port, input) in instruction.inputs.iter_mut().zip(state.inputs.iter()) {
+            save_port(port, input);
         }
-        for (i, output) in state.outputs.iter().enumerate() {
-            let rot = q_transform.get(*output).unwrap().rotation;
-            instruction.outputs[i].rotation = rot.to_euler(EulerRot::XYZ).2;
+        for (port, output) in instruction.outputs.iter_mut().zip(state.outputs.iter()) {
+            save_port(port, output);
         }
-        instructions.push(ModuleInfo {
+        Some(ModuleInfo {
             instructions: instruction,
             module: component.module.clone(),
-            module_type: component.ty,
-            offset: q_transform.get(module).unwrap().translation,
-        });
+            module_type: component.ty.clone(),
+            offset: q_transform.get(module).ok()?.translation,
+            blueprint: self.q_blueprint.get(module).ok().cloned(),
+        })
+    }
+}
+
+/// a save file, the machine the player built
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SaveData {
+    pub modules: Vec<ModuleInfo>,
+    #[serde(default)]
+    pub pieces: Vec<PieceInfo>,
+}
+
+impl From<MachineSnapshot> for SaveData {
+    fn from(MachineSnapshot { modules, pieces }: MachineSnapshot) -> Self {
+        SaveData {
+            modules: modules.into_iter().map(|(_, m)| m).collect(),
+            pieces: pieces.into_iter().map(|(_, p)| p).collect(),
+        }
+    }
+}
+
+/// everything in the machine along with the ids they had in the editor, so loading can be undone
+#[derive(Debug, Clone, Default)]
+pub struct MachineSnapshot {
+    pub modules: Vec<(EditorId, ModuleInfo)>,
+    pub pieces: Vec<(EditorId, PieceInfo)>,
+}
+
+/// everything needed to take a [`MachineSnapshot`] of the world
+#[derive(SystemParam)]
+pub struct MachineQuery<'w, 's> {
+    q_modules: Query<'w, 's, (Entity, &'static EditorId), (With<marker::Module>, Without<marker::Locked>)>,
+    q_pieces: Query<'w, 's, (Entity, &'static EditorId, &'static PieceKind, &'static Transform)>,
+    q_info: ModuleInfoQuery<'w, 's>,
+}
+
+impl<'w, 's> MachineQuery<'w, 's> {
+    pub fn snapshot(&self) -> MachineSnapshot {
+        MachineSnapshot {
+            modules: self.q_modules
+                .iter()
+                .filter_map(|(module, id)| Some((*id, self.q_info.get(module)?)))
+                .collect(),
+            pieces: self.q_pieces
+                .iter()
+                .map(|(_, id, kind, transform)| (*id, PieceInfo::of(*kind, transform)))
+                .collect(),
+        }
     }
 
+    /// every module and piece that gets replaced when something else is loaded
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.q_modules
+            .iter()
+            .map(|(e, _)| e)
+            .chain(self.q_pieces.iter().map(|(e, ..)| e))
+    }
+}
+
+pub fn save_world(
+    q_machine: MachineQuery,
+    mut save_events: EventReader<SaveWorld>,
+    slots: Res<SaveSlots>,
+) {
+    let Some(SaveWorld(name)) = save_events.iter().next() else {
+        return;
+    };
+    let path = slots.path(name);
+
+    let save = SaveFile::new(&SaveData::from(q_machine.snapshot()), slots.level.as_deref(), name);
+
     #[cfg(not(target_arch = "wasm32"))]
     IoTaskPool::get()
         .spawn(async move {
-            // #[allow(unused_assignments)]
-            let serialized = ron::ser::to_string(&instructions).unwrap();
-            // #[cfg(debug_assertions)]
-            // serialized = ron::ser
-            //     ::to_string_pretty(&instructions, PrettyConfig::default())
-            //     .unwrap();
-
-            // Write the scene RON data to file
-            let ret = File::create(format!("data/saves/{path}.ron")).and_then(|mut file|
-                file.write(serialized.as_bytes())
-            );
-            if ret.is_err() {
-                error!("Failed to save world to {path}")
+            if let Err(e) = write_save(&path, &save) {
+                error!("Failed to save world to {path:?}: {e}")
             }
         })
         .detach();
-} 
+}
+
+/// write a save file, making the folder its going in if it isnt there
+pub fn write_save(path: impl AsRef<Path>, save: &SaveFile) -> Result<(), LocatedError> {
+    let path = path.as_ref();
+    let serialized = ron::ser::to_string(save)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serialized)?;
+    Ok(())
+}
+
+/// save every so often while a level is being played
+pub fn autosave(
+    time: Res<Time>,
+    mut timer: Local<Option<Timer>>,
+    mut save_events: EventWriter<SaveWorld>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::from_seconds(AUTOSAVE_SECS, TimerMode::Repeating));
+    if timer.tick(time.delta()).just_finished() {
+        save_events.send(SaveWorld(AUTOSAVE.to_string()));
+    }
+}
+
+/// autosave right away when the game is closing, theres no time to do it in the background. this
+/// goes off in the same frame the window is asked to close, which is frames before bevy gets around
+/// to sending `AppExit` and the runner stops. saves whenever [`autosave`] would, sandbox included
+pub fn autosave_on_exit(
+    mut close_events: EventReader<WindowCloseRequested>,
+    mut exit_events: EventReader<AppExit>,
+    state: Res<CurrentState<GameState>>,
+    q_machine: MachineQuery,
+    slots: Res<SaveSlots>,
+    mut saved: Local<bool>,
+) {
+    let closing = close_events.iter().any(|e| e.id.is_primary());
+    let exiting = exit_events.iter().next().is_some();
+    if *saved || !(closing || exiting) || state.0 != GameState::Playing {
+        return;
+    }
+    *saved = true;
+    if let Err(e) = slots.write(AUTOSAVE, &SaveData::from(q_machine.snapshot())) {
+        error!("Failed to autosave: {e}");
+    }
+}
 
 pub struct LoadWorld(pub String);
 
+#[allow(clippy::too_many_arguments)]
 pub fn load_world(
-    q_modules: Query<Entity, With<marker::Module>>,
+    q_machine: MachineQuery,
     mut load_events: EventReader<LoadWorld>,
     mut commands: Commands,
     mut spawn_events: EventWriter<SpawnModule>,
+    mut spawn_pieces: EventWriter<SpawnPiece>,
+    mut record: EventWriter<RecordEdit>,
+    mut ids: ResMut<EditorIds>,
+    mut rng: ResMut<SimRng>,
+    mut slots: ResMut<SaveSlots>,
+    registry: Res<ModuleRegistry>,
 ) {
-    let Some(LoadWorld(path)) = load_events.iter().next() else {
+    let Some(LoadWorld(name)) = load_events.iter().next() else {
         return;
     };
-    let path = path.clone();
 
-    for module in q_modules.iter() {
-        commands.entity(module).despawn_recursive();
+    let save = match slots.read(name, &registry) {
+        Ok(save) => save,
+        Err(e) => {
+            error!("Failed to load world from {name}: {e}");
+            return;
+        }
+    };
+
+    // remember what was here so the load can be undone
+    let before = q_machine.snapshot();
+    for entity in q_machine.entities() {
+        commands.entity(entity).despawn_recursive();
     }
+    rng.reset();
 
-    let serialized = std::fs::read_to_string(format!("data/saves/{path}.ron")).unwrap();
-    let Ok(instructions) = ron::de::from_str::<Vec<ModuleInfo>>(&serialized) else {
-        error!("Failed to load world from {path}");
-        return;
+    let after = MachineSnapshot {
+        modules: save.modules
+            .into_iter()
+            .map(|module| (ids.next(), module))
+            .collect(),
+        pieces: save.pieces
+            .into_iter()
+            .map(|piece| (ids.next(), piece))
+            .collect(),
     };
-    
-    for module in instructions {
-        spawn_events.send(SpawnModule::new(module));
+    spawn_machine(&after, &mut spawn_events, &mut spawn_pieces);
+    record.send(RecordEdit(Edit::Load { before, after }));
+}
+
+/// spawn everything in a snapshot back in with the ids it had
+pub fn spawn_machine(
+    machine: &MachineSnapshot,
+    spawn_events: &mut EventWriter<SpawnModule>,
+    spawn_pieces: &mut EventWriter<SpawnPiece>
+) {
+    for (id, module) in machine.modules.iter() {
+        spawn_events.send(SpawnModule::new(module.clone()).with_id(*id));
     }
-}
\ No newline at end of file
+    for (id, piece) in machine.pieces.iter() {
+        spawn_pieces.send(SpawnPiece::new(piece.clone()).with_id(*id));
+    }
+}
+
+/// read a save file of any version, see [`save_file::parse`]
+pub fn read_save(path: impl AsRef<Path>, registry: &ModuleRegistry) -> Result<LoadedSave, LocatedError> {
+    let serialized = std::fs::read_to_string(path)?;
+    Ok(save_file::parse(&serialized, registry)?)
+}
+
+#[test]
+fn test_autosave_on_exit() {
+    use bevy::window::WindowId;
+
+    // somewhere out of the way so it doesnt end up next to the real saves
+    let root = std::env::temp_dir().join(format!("marble_saves_{}", std::process::id()));
+    let slots = SaveSlots {
+        root: root.clone(),
+        level: Some("test_autosave_on_exit".to_string()),
+        report: None,
+    };
+    let path = slots.path(AUTOSAVE);
+
+    let mut app = App::new();
+    app.add_event::<WindowCloseRequested>()
+        .add_event::<AppExit>()
+        .insert_resource(CurrentState(GameState::Playing))
+        .insert_resource(slots)
+        .add_system_to_stage(CoreStage::Last, autosave_on_exit);
+
+    app.update();
+    assert!(!path.exists());
+
+    // the window gets closed, the save has to be there by the end of that frame
+    app.world.send_event(WindowCloseRequested { id: WindowId::primary() });
+    app.update();
+    assert!(path.exists());
+    assert!(read_save(&path, &ModuleRegistry::default()).unwrap().report.is_clean());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
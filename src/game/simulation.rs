@@ -0,0 +1,274 @@
+use std::time::Duration;
+
+use bevy::{ time::TimeUpdateStrategy, utils::Instant };
+
+use crate::{
+    *,
+    engine::{
+        marble::Marble,
+        marble_io::{ FireMarbleEvent, MarblesFired },
+        module_state::ModuleState,
+        sim::{ self, AppSimExt, TickStage },
+        spawn::SpawnModule,
+        piece::SpawnPiece,
+    },
+    game::{
+        level::{ self, Level, ReceivedMarbles },
+        save_load::SaveData,
+    },
+    modules::{ ModuleCallbackTimer, ModuleEvent, ModuleRegistry },
+};
+
+/// ticks to let everything spawn in and get picked up by rapier before we fire anything
+pub const WARMUP_TICKS: usize = 2;
+
+/// everything that makes machines run, modules, marbles, physics, and nothing that draws them.
+/// the game adds it alongside the rendering, [`Simulation`] runs it on its own
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedModules>()
+            .init_resource::<ReceivedMarbles>()
+            .init_resource::<grid::GridInfo>()
+            .init_resource::<MarblesFired>()
+            .insert_resource(RapierConfiguration {
+                physics_pipeline_active: true,
+                query_pipeline_active: true,
+                timestep_mode: TimestepMode::Fixed {
+                    dt: sim::TICK,
+                    substeps: 1,
+                },
+                ..default()
+            })
+            // rapier steps inside of sim::SimTickStage instead of once a frame
+            .add_plugin(
+                RapierPhysicsPlugin::<NoUserData>
+                    ::pixels_per_meter(100.0)
+                    .with_default_system_setup(false)
+            )
+            .add_event::<FireMarbleEvent>()
+            .add_event::<modules::UpdateModule>()
+            .add_event::<SpawnModule>()
+            .add_event::<SpawnPiece>();
+
+        engine::app(app);
+        modules::app(app);
+        app.add_tick_system_set(
+            TickStage::Logic,
+            SystemSet::new().with_system(level::collect_outputs.after("marble_io::update_inputs"))
+        );
+    }
+}
+
+/// a machine running without a window, stepped one tick at a time by whoever owns it
+pub struct Simulation {
+    app: App,
+    now: Instant,
+    ticks: usize,
+    outputs: usize,
+}
+
+impl Simulation {
+    /// a machine on its own with no level hooked up to it, so theres nothing for [`Simulation::inject`]
+    /// to fire out of. use [`Simulation::for_level`] to run it through a level
+    pub fn load(save: &SaveData, registry: &ModuleRegistry) -> Self {
+        Self::with_ports(save, 0, 0, registry)
+    }
+
+    /// a machine along with the ports of a level
+    pub fn for_level(save: &SaveData, level: &Level, registry: &ModuleRegistry) -> Self {
+        Self::with_ports(save, level.inputs.len(), level.outputs.len(), registry)
+    }
+
+    /// a machine along with this many level inputs and outputs for it to hook up to. it gets its
+    /// own copy of `registry`, so whatever made it can keep registering modules without it noticing
+    pub fn with_ports(save: &SaveData, inputs: usize, outputs: usize, registry: &ModuleRegistry) -> Self {
+        let mut app = App::new();
+        app.insert_resource(registry.clone())
+            .add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(SimulationPlugin);
+
+        for module in save.modules.iter() {
+            app.world.send_event(SpawnModule::new(module.clone()));
+        }
+        for piece in save.pieces.iter() {
+            app.world.send_event(SpawnPiece::new(piece.clone()));
+        }
+        let grid_info = grid::GridInfo::default();
+        for event in level::port_modules(inputs, outputs, &grid_info, registry) {
+            app.world.send_event(event);
+        }
+
+        let mut simulation = Simulation { app, now: Instant::now(), ticks: 0, outputs };
+        simulation.step(WARMUP_TICKS);
+        simulation.ticks = 0;
+        simulation
+    }
+
+    /// step forward `n` ticks, or `n` times however many ticks `SimControl::speed` is if thats been
+    /// turned up
+    pub fn step(&mut self, n: usize) {
+        // move the clock by exactly one physics tick every update
+        let dt = Duration::from_secs_f32(sim::TICK);
+        for _ in 0..n {
+            self.now += dt;
+            self.app.insert_resource(TimeUpdateStrategy::ManualInstant(self.now));
+            self.app.update();
+            self.ticks += 1;
+        }
+    }
+
+    /// how many ticks its been stepped since it was loaded
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    /// how many marbles have been fired since it was loaded, ones fired into it included
+    pub fn marbles_fired(&self) -> usize {
+        self.app.world.resource::<MarblesFired>().0
+    }
+
+    /// fire a marble out of one of the level inputs, `false` if theres no such input
+    pub fn inject(&mut self, input_port: usize, marble: Marble) -> bool {
+        let world = &mut self.app.world;
+        let output = world
+            .query::<(&marker::LevelInput, &ModuleState)>()
+            .iter(world)
+            .find(|(&marker::LevelInput(n), _)| n == input_port)
+            .and_then(|(_, state)| state.outputs.first().copied());
+        let Some(output) = output else {
+            return false;
+        };
+        world.send_event(FireMarbleEvent::new(marble, output, 1.0));
+        true
+    }
+
+    /// every marble thats reached each level output since the last time this was called
+    pub fn drain_outputs(&mut self) -> Vec<Vec<Marble>> {
+        let mut received = std::mem::take(&mut **self.app.world.resource_mut::<ReceivedMarbles>());
+        received.resize(self.outputs.max(received.len()), vec![]);
+        received
+    }
+
+    /// whether nothing is going on, no marbles flying, no modules waiting to do something
+    pub fn is_idle(&mut self) -> bool {
+        let world = &mut self.app.world;
+        world.query_filtered::<(), With<Marble>>().iter(world).next().is_none() &&
+            world.query::<&ModuleCallbackTimer>().iter(world).next().is_none() &&
+            world.resource::<Events<FireMarbleEvent>>().is_empty() &&
+            world.resource::<Events<ModuleEvent>>().is_empty()
+    }
+
+    /// the world its running in, for anything the methods here dont cover
+    pub fn world(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+}
+
+#[test]
+fn test_simulation() {
+    use crate::game::{ save_load::ModuleInfo, level::LevelPort };
+
+    // where a marble comes out of an output, and where it has to be to go into an input
+    fn muzzle(world: &mut World, output: Entity) -> Vec3 {
+        let mut q_muzzle = world.query_filtered::<(&Parent, &GlobalTransform), With<marker::Muzzle>>();
+        let (_, transform) = q_muzzle.iter(world).find(|(parent, _)| parent.get() == output).unwrap();
+        transform.translation().truncate().extend(0.0)
+    }
+    fn sensor(world: &mut World, input: Entity) -> Vec3 {
+        let mut q_sensor = world.query_filtered::<(&Parent, &GlobalTransform), With<Sensor>>();
+        let (_, transform) = q_sensor.iter(world).find(|(parent, _)| parent.get() == input).unwrap();
+        transform.translation().truncate().extend(0.0)
+    }
+    // the inputs and outputs of a level port, or of the one module that isnt one
+    fn ports(world: &mut World, port: Option<LevelPort>) -> (Vec<Entity>, Vec<Entity>) {
+        let mut q_module = world.query::<(&ModuleState, Option<&marker::LevelInput>, Option<&marker::LevelOutput>)>();
+        let (state, ..) = q_module
+            .iter(world)
+            .find(|(_, input, output)| match port {
+                Some(LevelPort::Input(n)) => input.map_or(false, |input| input.0 == n),
+                Some(LevelPort::Output(n)) => output.map_or(false, |output| output.0 == n),
+                None => input.is_none() && output.is_none(),
+            })
+            .unwrap();
+        (state.inputs.clone(), state.outputs.clone())
+    }
+
+    let registry = ModuleRegistry::with_builtin();
+    let mut sender = ModuleInfo::new(registry.get("sender.teleporter").unwrap());
+    let mut receiver = ModuleInfo::new(registry.get("receiver.teleporter").unwrap());
+
+    // wire input 0 to output 0 with a pair of teleporters, the sender sitting right where the
+    // level input fires and the receiver firing right into the level output
+    let mut probe = Simulation::with_ports(
+        &SaveData { modules: vec![sender.clone()], pieces: vec![] }, 1, 2, &registry
+    );
+    let world = probe.world();
+    let (_, level_input) = ports(world, Some(LevelPort::Input(0)));
+    let (level_output, _) = ports(world, Some(LevelPort::Output(0)));
+    let (sender_input, _) = ports(world, None);
+    let fired_at = muzzle(world, level_input[0]);
+    let goes_into = sensor(world, level_output[0]);
+    sender.offset = fired_at - sensor(world, sender_input[0]);
+
+    let mut probe = Simulation::with_ports(
+        &SaveData { modules: vec![receiver.clone()], pieces: vec![] }, 0, 0, &registry
+    );
+    let world = probe.world();
+    let (_, receiver_output) = ports(world, None);
+    receiver.offset = goes_into - muzzle(world, receiver_output[0]);
+
+    let save = SaveData { modules: vec![sender, receiver], pieces: vec![] };
+    let mut simulation = Simulation::with_ports(&save, 1, 2, &registry);
+    assert!(simulation.inject(0, Marble::bit(true)));
+    assert!(!simulation.inject(1, Marble::bit(true)));
+
+    let mut received = vec![vec![]; 2];
+    for _ in 0..120 {
+        simulation.step(1);
+        for (port, marbles) in simulation.drain_outputs().into_iter().enumerate() {
+            received[port].extend(marbles);
+        }
+    }
+    assert_eq!(simulation.ticks(), 120);
+    assert_eq!(received[0].len(), 1);
+    assert!(received[0][0].get_bit());
+    assert!(received[1].is_empty());
+}
+
+#[test]
+fn test_fast_forward() {
+    use crate::{ game::save_load::ModuleInfo, engine::sim::SimControl };
+
+    let registry = ModuleRegistry::with_builtin();
+    let save = SaveData {
+        modules: vec![ModuleInfo::new(registry.get("basic.module").unwrap())],
+        pieces: vec![],
+    };
+
+    // put a marble in a module so theres a callback before it gets fired, then write down where
+    // everything is every 16 ticks
+    let run = |speed: u32| {
+        let mut simulation = Simulation::load(&save, &registry);
+        let world = simulation.world();
+        world.query::<&mut ModuleState>().single_mut(world).input_state[0] = Some(Marble::bit(true));
+        world.resource_mut::<SimControl>().speed = speed;
+
+        let mut positions = vec![];
+        for _ in 0..10 {
+            // every step is a frame, which is `speed` ticks
+            simulation.step((16 / speed) as usize);
+            let world = simulation.world();
+            let mut q_marbles = world.query_filtered::<&Transform, With<Marble>>();
+            positions.push(q_marbles.iter(world).map(|t| t.translation).collect::<Vec<_>>());
+        }
+        positions
+    };
+
+    let normal = run(1);
+    assert!(normal.iter().any(|marbles| !marbles.is_empty()));
+    assert_eq!(normal, run(16));
+}
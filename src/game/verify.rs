@@ -0,0 +1,231 @@
+use std::{ fmt, path::Path, sync::{ Arc, Mutex } };
+
+use bevy::tasks::AsyncComputeTaskPool;
+
+use serde::Serialize;
+
+use crate::{
+    *,
+    game::{
+        level::{ CaseMarbles, Level },
+        save_file::LoadReport,
+        save_load::{ self, SaveData },
+        simulation::Simulation,
+    },
+    modules::ModuleRegistry,
+};
+
+/// how many ticks a single case gets before we give up on it
+pub const MAX_TICKS: usize = 60 * 20;
+/// how many ticks in a row nothing has to be happening before we call the case done
+const IDLE_TICKS: usize = 10;
+/// so a `generate` that never returns nil doesnt hang us forever
+const MAX_CASES: usize = 256;
+
+/// the result of running one test case
+#[derive(Debug, Serialize)]
+pub struct CaseReport {
+    pub inputs: CaseMarbles,
+    pub outputs: CaseMarbles,
+    pub ticks: usize,
+    /// every marble fired while it ran, the inputs included
+    pub marbles: usize,
+    pub passed: bool,
+}
+
+/// the result of running every test case of a level against a machine
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    pub level: String,
+    /// how many modules the machine is made of
+    pub modules: usize,
+    /// how reading the save went, if it came from a file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load: Option<LoadReport>,
+    pub cases: Vec<CaseReport>,
+}
+
+impl VerifyReport {
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(|case| case.passed)
+    }
+
+    /// how many ticks every case took put together
+    pub fn total_ticks(&self) -> usize {
+        self.cases.iter().map(|case| case.ticks).sum()
+    }
+
+    /// how many marbles every case used put together
+    pub fn total_marbles(&self) -> usize {
+        self.cases.iter().map(|case| case.marbles).sum()
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "level \"{}\"", self.level)?;
+        for (i, case) in self.cases.iter().enumerate() {
+            writeln!(
+                f,
+                "  case {:>3}: {} ({} ticks, {} marbles) inputs: {:?} outputs: {:?}",
+                i + 1,
+                ["FAIL", "pass"][case.passed as usize],
+                case.ticks,
+                case.marbles,
+                *case.inputs,
+                *case.outputs
+            )?;
+        }
+        let passed = self.cases.iter().filter(|case| case.passed).count();
+        write!(
+            f,
+            "{passed}/{} cases passed, {} ticks, {} marbles, {} modules",
+            self.cases.len(),
+            self.total_ticks(),
+            self.total_marbles(),
+            self.modules
+        )
+    }
+}
+
+/// run a single case in a fresh world and return what came out, how long it took and how many
+/// marbles it went through
+fn run_case(
+    save: &SaveData,
+    level: &Level,
+    inputs: &CaseMarbles,
+    registry: &ModuleRegistry
+) -> (CaseMarbles, usize, usize) {
+    let mut simulation = Simulation::for_level(save, level, registry);
+
+    for (i, marble) in inputs.iter().enumerate() {
+        if let Some(marble) = marble {
+            simulation.inject(i, *marble);
+        }
+    }
+
+    // and wait for them to come out the other side
+    let mut received = vec![vec![]; level.outputs.len()];
+    let mut idle = 0;
+    while simulation.ticks() < MAX_TICKS {
+        simulation.step(1);
+
+        for (all, new) in received.iter_mut().zip(simulation.drain_outputs()) {
+            all.extend(new);
+        }
+        if received.iter().all(|r| !r.is_empty()) {
+            break;
+        }
+
+        idle = if simulation.is_idle() { idle + 1 } else { 0 };
+        if idle >= IDLE_TICKS {
+            break;
+        }
+    }
+
+    let outputs = received
+        .iter()
+        .map(|r| r.first().copied())
+        .collect();
+    (CaseMarbles(outputs), simulation.ticks(), simulation.marbles_fired())
+}
+
+/// run every case `level.generate` produces through the machine in the save file and grade the
+/// results with `level.test`
+pub fn verify_level(
+    level_path: impl AsRef<Path>,
+    save_path: impl AsRef<Path>,
+    registry: &ModuleRegistry
+) -> Result<VerifyReport, LocatedError> {
+    let lua = mlua::Lua::new();
+    let level = Level::load(&lua, level_path.as_ref(), registry)?;
+    let save = save_load::read_save(save_path, registry)?;
+    let mut report = verify_machine(&level, &save.data, registry)?;
+    report.load = Some(save.report);
+    Ok(report)
+}
+
+/// run every case of a level that's already loaded through this machine
+pub fn verify_machine(
+    level: &Level,
+    save: &SaveData,
+    registry: &ModuleRegistry
+) -> Result<VerifyReport, LocatedError> {
+    let mut cases = vec![];
+    for i in 1..=MAX_CASES {
+        let Some(inputs) = level.generate_case(i)? else {
+            break;
+        };
+        let (outputs, ticks, marbles) = run_case(save, level, &inputs, registry);
+        let passed = level.test_case(&outputs, &inputs)?;
+        cases.push(CaseReport { inputs, outputs, ticks, marbles, passed });
+    }
+
+    Ok(VerifyReport { level: level.name.clone(), modules: save.modules.len(), load: None, cases })
+}
+
+/// a [`verify_machine`] running on the async compute pool so the game keeps going while it does,
+/// a level can take a good while to get through every case
+pub struct VerifyTask {
+    result: Arc<Mutex<Option<Result<VerifyReport, LocatedError>>>>,
+}
+
+impl VerifyTask {
+    /// start running every case of `level` through this machine. the level gets loaded again from
+    /// its file with a lua of its own since the one the game uses cant leave the main thread
+    pub fn spawn(level: &Level, save: SaveData, registry: &ModuleRegistry) -> Self {
+        let result = Arc::new(Mutex::new(None));
+        let path = level.path.clone();
+        let registry = registry.clone();
+        let slot = result.clone();
+        AsyncComputeTaskPool::get()
+            .spawn(async move {
+                let lua = mlua::Lua::new();
+                let report = Level::load(&lua, &path, &registry)
+                    .and_then(|level| verify_machine(&level, &save, &registry));
+                *slot.lock().unwrap() = Some(report);
+            })
+            .detach();
+        Self { result }
+    }
+
+    /// the report once its done, `None` while its still running
+    pub fn poll(&self) -> Option<Result<VerifyReport, LocatedError>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+#[test]
+fn test_deterministic() {
+    use crate::{
+        game::save_load::ModuleInfo,
+        engine::{ marble::Marble, marble_io::FireMarbleEvent, module_state::ModuleState },
+    };
+
+    let registry = ModuleRegistry::with_builtin();
+    let save = SaveData {
+        modules: vec![ModuleInfo::new(registry.get("basic.module").unwrap())],
+        pieces: vec![],
+    };
+
+    // fire a marble out of a module and write down where it goes
+    let trajectory = || {
+        let mut simulation = Simulation::load(&save, &registry);
+        let world = simulation.world();
+        let output = world.query::<&ModuleState>().single(world).outputs[0];
+        world.send_event(FireMarbleEvent::new(Marble::bit(true), output, 1.0));
+
+        let mut positions = vec![];
+        for _ in 0..120 {
+            simulation.step(1);
+            let world = simulation.world();
+            let mut q_marbles = world.query_filtered::<&Transform, With<Marble>>();
+            positions.extend(q_marbles.iter(world).map(|t| t.translation));
+        }
+        positions
+    };
+
+    let first = trajectory();
+    assert!(!first.is_empty());
+    assert_eq!(first, trajectory());
+}
@@ -1,37 +1,43 @@
+use std::any::TypeId;
+
 use bevy::{log::info, prelude::*};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 // use crate::misc::builder_fn;
 
-/// implement on an enum to have it be a valid key to an atlas
+/// implement on an enum to have it be a valid key to an atlas, then add it to the
+/// [`AtlasRegistry`] so it gets loaded
 pub trait AtlasDictionary
 where
-    Self: Sized + Copy + Clone,
+    Self: Sized + Copy + Clone + 'static,
 {
-    fn atlas_rect() -> Vec2 {
-        unsafe { ATLAS_SIZE[Self::atlas_n()] }
-    }
-
+    /// the size of the whole atlas
+    fn atlas_rect() -> Vec2;
     /// the rect each individual item represents
     fn rect(self) -> Rect;
     /// the path to the atlas this enum is referring to
     fn path() -> String;
-    /// the id of this atlas (which order we init it)
-    fn atlas_n() -> usize;
     /// the id of this item in the atlas
     fn index(self) -> usize;
 
     /// get the texture handle for this atlas
+    /// (headless apps never init the atlases, so they just get the default handle)
     fn get() -> Handle<TextureAtlas> {
-        unsafe { ATLAS_HANDLES[Self::atlas_n()].clone() }
+        unsafe {
+            ATLAS_HANDLES
+                .iter()
+                .find(|(id, _)| *id == TypeId::of::<Self>())
+                .map(|(_, handle)| handle.clone())
+                .unwrap_or_default()
+        }
     }
 
     /// get the information needed to create a sprite
     /// will return the handle, the id, and the size of the specific sprite
     ///
-    /// ```
-    /// let (texture_atlas, index) = atlas::get_sprite(atlas::item1);
+    /// ```ignore
+    /// let (texture_atlas, index) = atlas::basic::marble.info();
     /// ```
     fn info(self) -> (Handle<TextureAtlas>, usize) {
         (Self::get(), self.index())
@@ -77,39 +83,64 @@ where
 const GRID_SIZE: f32 = 8.0;
 
 /// kinda bad but its the easiest way i could think of
-/// stores every handle for all the atlases we init
-static mut ATLAS_HANDLES: Vec<Handle<TextureAtlas>> = vec![];
-static mut ATLAS_SIZE: Vec<Vec2> = vec![];
+/// stores every handle for all the atlases we init, by the type of their dictionary
+static mut ATLAS_HANDLES: Vec<(TypeId, Handle<TextureAtlas>)> = vec![];
+
+type InitAtlas = fn(&mut Assets<TextureAtlas>, &AssetServer);
+
+/// every atlas that gets loaded on startup, anything added after that is too late
+#[derive(Resource)]
+pub struct AtlasRegistry {
+    atlases: Vec<InitAtlas>,
+}
+
+impl Default for AtlasRegistry {
+    fn default() -> Self {
+        let mut registry = AtlasRegistry { atlases: vec![] };
+        registry.add::<basic>();
+        registry
+    }
+}
+
+impl AtlasRegistry {
+    pub fn add<T: AtlasDictionary + IntoEnumIterator>(&mut self) {
+        self.atlases.push(init_atlas::<T>);
+    }
+}
+
+fn init_atlas<T: AtlasDictionary + IntoEnumIterator>(
+    texture_atlases: &mut Assets<TextureAtlas>,
+    asset_server: &AssetServer,
+) {
+    let mut atlas = TextureAtlas::new_empty(asset_server.load(T::path()), T::atlas_rect());
+    T::iter().for_each(|variant| {
+        let rect = variant.rect();
+        atlas.add_texture(rect);
+    });
+    let handle = texture_atlases.add(atlas);
+    unsafe {
+        ATLAS_HANDLES.push((TypeId::of::<T>(), handle));
+    }
+}
 
 pub fn init_texture_atlas(
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     asset_server: Res<AssetServer>,
+    registry: Res<AtlasRegistry>,
 ) {
     info!("Texture atlases are being initialized");
-    macro init_atlas($target:ty, $dimensions:expr) {{
-        let mut atlas =
-            TextureAtlas::new_empty(asset_server.load(<$target>::path()), $dimensions.into());
-        <$target>::iter().for_each(|variant| {
-            let rect = variant.rect();
-            atlas.add_texture(rect);
-        });
-        let handle = texture_atlases.add(atlas);
-        unsafe {
-            ATLAS_HANDLES.push(handle);
-            ATLAS_SIZE.push($dimensions.into());
-        }
-    }}
-
-    init_atlas!(basic, [64.0, 64.0]);
+    for init in registry.atlases.iter() {
+        init(&mut texture_atlases, &asset_server);
+    }
 }
 
-macro default_impl_atlas_dictionary($t:ty, $n:expr) {
+macro default_impl_atlas_dictionary($t:ty, $dimensions:expr) {
+    fn atlas_rect() -> Vec2 {
+        $dimensions.into()
+    }
     fn path() -> String {
         stringify!($t).to_string() + ".png"
     }
-    fn atlas_n() -> usize {
-        $n
-    }
     fn index(self) -> usize {
         self as usize
     }
@@ -125,6 +156,8 @@ pub enum basic {
     marble_input,
     body_small,
     body,
+    body_wide,
+    body_block,
     target,
     tracer_tick,
     indicator,
@@ -143,6 +176,8 @@ impl AtlasDictionary for basic {
             marble_input  => (0, 4, 8, 12),
             body_small    => (3, 0, 14, 14),
             body          => (5, 0, 22, 22),
+            body_wide     => (1, 6, 30, 14),
+            body_block    => (5, 4, 22, 22),
             target        => (1, 1, 7, 7),
             tracer_tick   => (2, 1, 2, 2),
             indicator     => (0, 1, 3, 3),
@@ -156,5 +191,5 @@ impl AtlasDictionary for basic {
         )
     }
 
-    default_impl_atlas_dictionary!(basic, 0);
+    default_impl_atlas_dictionary!(basic, [64.0, 64.0]);
 }
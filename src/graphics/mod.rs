@@ -4,8 +4,10 @@ pub mod atlas;
 pub mod grid;
 
 pub fn app(app: &mut App) {
-    app.add_startup_system_to_stage(StartupStage::PreStartup, atlas::init_texture_atlas)
+    app.init_resource::<atlas::AtlasRegistry>()
+        .add_startup_system_to_stage(StartupStage::PreStartup, atlas::init_texture_atlas)
         .insert_resource(grid::GridInfo::default())
         .insert_resource(ClearColor(Color::hsl(216.0, 0.24, 0.55)))
-        .add_system(grid::spawn_background);
+        .add_system(grid::spawn_background)
+        .add_system_to_stage(CoreStage::PostUpdate, marble::marble_sprites);
 }
\ No newline at end of file
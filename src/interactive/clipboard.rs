@@ -0,0 +1,138 @@
+use bevy_egui::EguiContext;
+use ron::ser::PrettyConfig;
+
+use crate::{
+    *,
+    engine::{ spawn::SpawnModule, piece::{ PieceInfo, PieceKind, SpawnPiece } },
+    modules::ModuleRegistry,
+    game::{ save_load::{ ModuleInfoQuery, SaveData }, blueprint },
+    query::QueryQuerySimple,
+};
+
+use super::history::{ RecordEdit, Edit };
+
+/// copied modules and pieces, kept as ron text so it can be passed around outside of the game too
+#[derive(Resource, Default, Debug)]
+pub struct Clipboard {
+    pub text: String,
+}
+
+impl Clipboard {
+    /// whats on the clipboard, offsets are relative to whatever was the main selected one
+    pub fn machine(&self, registry: &ModuleRegistry) -> Result<SaveData, ron::error::SpannedError> {
+        registry.scope(|| ron::de::from_str(&self.text))
+    }
+
+    pub fn set(&mut self, machine: &SaveData) {
+        match ron::ser::to_string_pretty(machine, PrettyConfig::default()) {
+            Ok(text) => self.text = text,
+            Err(e) => error!("couldnt copy modules: {e}"),
+        }
+    }
+}
+
+/// snapshot everything thats selected, moved so the main selected module or piece is at the origin
+fn snapshot(
+    selected: &SelectedModules,
+    q_info: &ModuleInfoQuery,
+    q_piece: &Query<(&PieceKind, &Transform)>,
+    has_locked: &Query<With<marker::Locked>>
+) -> SaveData {
+    let unlocked = || selected.all().filter(|e| !has_locked.has(*e));
+    let mut machine = SaveData {
+        modules: unlocked().filter_map(|e| q_info.get(e)).collect(),
+        pieces: unlocked()
+            .filter_map(|e| q_piece.get(e).ok())
+            .map(|(kind, transform)| PieceInfo::of(*kind, transform))
+            .collect(),
+    };
+    let origin = unlocked().next().and_then(|e| match q_info.get(e) {
+        Some(module) => Some(module.offset),
+        None => q_piece.get(e).ok().map(|(_, transform)| transform.translation),
+    });
+    if let Some(origin) = origin {
+        machine.modules.iter_mut().for_each(|m| m.offset -= origin);
+        machine.pieces.iter_mut().for_each(|p| p.offset -= origin);
+    }
+    machine
+}
+
+/// spawn all of it in place mode, one of it under the cursor and the rest following it. a piece
+/// gets to be the one under the cursor if theres any so everything stays on the grid
+fn paste(
+    mut machine: SaveData,
+    spawn_events: &mut EventWriter<SpawnModule>,
+    spawn_pieces: &mut EventWriter<SpawnPiece>
+) {
+    blueprint::fresh_instances(&mut machine.modules);
+    let mut placing = false;
+    for piece in machine.pieces {
+        let spawn = SpawnPiece::new(piece);
+        spawn_pieces.send(if placing { spawn.follow() } else { spawn.place() });
+        placing = true;
+    }
+    for module in machine.modules {
+        let spawn = SpawnModule::new(module);
+        spawn_events.send(if placing { spawn.follow() } else { spawn.place() });
+        placing = true;
+    }
+}
+
+/// ctrl+c / ctrl+x / ctrl+v / ctrl+d
+#[allow(clippy::too_many_arguments)]
+pub fn clipboard_keys(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut clipboard: ResMut<Clipboard>,
+    mut selected: ResMut<SelectedModules>,
+    q_info: ModuleInfoQuery,
+    q_piece: Query<(&PieceKind, &Transform)>,
+    has_locked: Query<With<marker::Locked>>,
+    mut spawn_events: EventWriter<SpawnModule>,
+    mut spawn_pieces: EventWriter<SpawnPiece>,
+    mut record: EventWriter<RecordEdit>,
+    registry: Res<ModuleRegistry>,
+) {
+    // text boxes want these keys more
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if !keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::C) {
+        let machine = snapshot(&selected, &q_info, &q_piece, &has_locked);
+        if !machine.modules.is_empty() || !machine.pieces.is_empty() {
+            clipboard.set(&machine);
+        }
+    } else if keyboard.just_pressed(KeyCode::X) {
+        let machine = snapshot(&selected, &q_info, &q_piece, &has_locked);
+        if machine.modules.is_empty() && machine.pieces.is_empty() {
+            return;
+        }
+        clipboard.set(&machine);
+
+        let doomed = selected
+            .all()
+            .filter(|e| !has_locked.has(*e))
+            .collect::<Vec<_>>();
+        record.send(
+            RecordEdit(Edit::Group(doomed.iter().map(|&entity| Edit::Delete { entity }).collect()))
+        );
+        for e in doomed {
+            commands.entity(e).despawn_recursive();
+        }
+        selected.clear_selected();
+    } else if keyboard.just_pressed(KeyCode::V) {
+        match clipboard.machine(&registry) {
+            Ok(machine) => paste(machine, &mut spawn_events, &mut spawn_pieces),
+            Err(e) => error!("clipboard doesnt have modules on it: {e}"),
+        }
+    } else if keyboard.just_pressed(KeyCode::D) {
+        // like copy and paste but leaves the clipboard alone
+        let machine = snapshot(&selected, &q_info, &q_piece, &has_locked);
+        paste(machine, &mut spawn_events, &mut spawn_pieces);
+    }
+}
@@ -1,10 +1,11 @@
 use std::f32::consts::TAU;
 
-use crate::{query::QueryQuerySimple, *};
+use crate::{ query::QueryQuerySimple, *, engine::{ module_state::ModuleState, piece::PieceKind } };
 
 use super::{
+    history::{ RecordEdit, Edit },
     interact::InteractiveRotation,
-    intersect::{MoveType, RequestedMove},
+    intersect::{ MoveType, RequestedMove },
     select::CursorCoords,
 };
 
@@ -23,8 +24,18 @@ pub fn drag_selected(
     mut q_interactive_rot: Query<&mut InteractiveRotation>,
     mut prev: Local<Vec2>,
     grid_info: Res<grid::GridInfo>,
+    has_locked: Query<With<marker::Locked>>,
+    mut record: EventWriter<RecordEdit>,
+    mut from: Local<Vec<(Entity, Vec3)>>,
+    has_piece: Query<With<PieceKind>>,
     // returns an option to pipe into
 ) {
+    let followers = selected.group
+        .iter()
+        .copied()
+        .filter(|e| !has_locked.has(*e))
+        .collect::<Vec<_>>();
+
     let snapping = if keyboard.pressed(KeyCode::LShift) {
         8.0
     } else {
@@ -33,9 +44,14 @@ pub fn drag_selected(
 
     // basically: if active is not true it needs these specific conditions to become true, or else the system will not run
     if !*active {
-        if selected.is_changed() && mouse_buttons.pressed(MouseButton::Left) && let Some(selected) = selected.selected{
+        if selected.is_changed() && mouse_buttons.pressed(MouseButton::Left) && let Some(selected) = selected.selected && !has_locked.has(selected) {
             *active = true;
-            *starting_pos = **mouse_pos - q_transform.entity(selected).translation.truncate();
+            let translation = q_transform.entity(selected).translation;
+            *starting_pos = **mouse_pos - translation.truncate();
+            *from = std::iter::once(selected)
+                .chain(followers.iter().copied())
+                .map(|e| (e, q_transform.entity(e).translation))
+                .collect();
         } else {
             *active = false;
             return;
@@ -45,33 +61,140 @@ pub fn drag_selected(
     // if we let go of the left mouse button, return
     if !mouse_buttons.pressed(MouseButton::Left) {
         *active = false;
+        // the whole drag is one undo step
+        let moved = from
+            .drain(..)
+            .filter(|(entity, from)| q_transform.get(*entity).map_or(false, |t| t.translation != *from))
+            .map(|(entity, from)| Edit::Move { entity, from })
+            .collect::<Vec<_>>();
+        if !moved.is_empty() {
+            record.send(RecordEdit(Edit::Group(moved)));
+        }
         return;
     }
 
     let Some(selected) = selected.selected else {*active = false; return};
 
-    if let Ok(mut i_rot) = q_interactive_rot.get_mut(selected) {
+    // groups get turned in rotate_group
+    if followers.is_empty() && let Ok(mut i_rot) = q_interactive_rot.get_mut(selected) {
+        let before = i_rot.clone();
         if keyboard.just_pressed(KeyCode::Q) {
             i_rot.rot += TAU / 8.0;
         } else if keyboard.just_pressed(KeyCode::E) {
             i_rot.rot -= TAU / 8.0;
         }
+        if *i_rot != before {
+            record.send(RecordEdit(Edit::Rotate { entity: selected, before }));
+        }
     }
 
-    let Vec2 { x, y } = **mouse_pos - *starting_pos - 0.5;
+    let piece = has_piece.has(selected);
+    if followers.is_empty() && piece {
+        let angle = if keyboard.just_pressed(KeyCode::Q) {
+            TAU / 8.0
+        } else if keyboard.just_pressed(KeyCode::E) {
+            -TAU / 8.0
+        } else {
+            0.0
+        };
+        if angle != 0.0 {
+            let from = q_transform.entity(selected).rotation;
+            requested_move.send(
+                RequestedMove::new(selected, MoveType::RotateTo(Quat::from_rotation_z(angle) * from))
+            );
+            record.send(RecordEdit(Edit::Turn { entity: selected, from }));
+        }
+    }
+
+    let round = if piece {
+        // pieces sit on the grid
+        let grid = grid_info.grid_size;
+        ((**mouse_pos - *starting_pos) / grid).round() * grid
+    } else {
+        let Vec2 { x, y } = **mouse_pos - *starting_pos - 0.5;
 
-    // rounding x and y to the nearest snapping #
-    let round = Vec2::new(
-        (x / snapping).round() * snapping + 0.5,
-        (y / snapping).round() * snapping + 0.5,
-    );
+        // rounding x and y to the nearest snapping #
+        Vec2::new(
+            (x / snapping).round() * snapping + 0.5,
+            (y / snapping).round() * snapping + 0.5,
+        )
+    };
     // let round = Vec2::new(x, y);
 
     if round != *prev && f32::max(round.x.abs(), round.y.abs()) < grid_info.half_size {
-        requested_move.send(
-            RequestedMove::new(selected, MoveType::TranslateTo(round.extend(0.0))).snapping(),
-        );
+        let request = RequestedMove::new(selected, MoveType::TranslateTo(round.extend(0.0)))
+            .with_followers(followers);
+        requested_move.send(if piece { request.grid(grid_info.grid_size) } else { request.snapping() });
 
         *prev = round;
     }
 }
+
+/// Q / E while dragging a group around swings the whole thing around the main selected module,
+/// if any of it would end up inside of something else none of it turns
+#[allow(clippy::too_many_arguments)]
+pub fn rotate_group(
+    selected: Res<SelectedModules>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    has_locked: Query<With<marker::Locked>>,
+    q_transform: Query<&Transform>,
+    q_module_state: Query<&ModuleState>,
+    mut requested_move: EventWriter<RequestedMove>,
+    mut record: EventWriter<RecordEdit>,
+    // whether it fit only shows up the frame after, so the edits wait until then
+    mut pending: Local<Option<(Entity, Quat, Vec<Edit>)>>,
+) {
+    if let Some((turned, from, edits)) = pending.take() &&
+        q_transform.get(turned).map_or(false, |t| t.rotation != from)
+    {
+        record.send(RecordEdit(Edit::Group(edits)));
+    }
+
+    if selected.group.is_empty() || !mouse_buttons.pressed(MouseButton::Left) {
+        return;
+    }
+    let angle = if keyboard.just_pressed(KeyCode::Q) {
+        TAU / 8.0
+    } else if keyboard.just_pressed(KeyCode::E) {
+        -TAU / 8.0
+    } else {
+        return;
+    };
+    let Some(main) = selected.selected.filter(|e| !has_locked.has(*e)) else {
+        return;
+    };
+    let followers = selected.group
+        .iter()
+        .copied()
+        .filter(|e| *e != main && !has_locked.has(*e))
+        .collect::<Vec<_>>();
+
+    // modules dont turn themselves, just their body and ports, pieces turn the whole thing
+    let mut edits = vec![];
+    for entity in std::iter::once(main).chain(followers.iter().copied()) {
+        let Ok(transform) = q_transform.get(entity) else {
+            continue;
+        };
+        if entity != main {
+            edits.push(Edit::Move { entity, from: transform.translation });
+        }
+        match q_module_state.get(entity) {
+            Ok(state) => {
+                let get = |e: &Entity| q_transform.get(*e).ok();
+                if let Some(before) = InteractiveRotation::of(state, get) {
+                    edits.push(Edit::Rotate { entity, before });
+                }
+            }
+            Err(_) => edits.push(Edit::Turn { entity, from: transform.rotation }),
+        }
+    }
+
+    let turned = q_module_state.get(main).map_or(main, |state| state.body);
+    let Ok(&Transform { rotation, .. }) = q_transform.get(turned) else {
+        return;
+    };
+    let to = Quat::from_rotation_z(angle) * q_transform.entity(main).rotation;
+    requested_move.send(RequestedMove::new(main, MoveType::RotateTo(to)).with_followers(followers));
+    *pending = Some((turned, rotation, edits));
+}
@@ -0,0 +1,397 @@
+use bevy::ecs::system::SystemParam;
+use bevy_egui::EguiContext;
+
+use crate::{
+    *,
+    engine::{ module_state::ModuleState, spawn::{ EditorId, SpawnModule } },
+    engine::piece::{ PieceInfo, PieceKind, SpawnPiece },
+    game::save_load::{ ModuleInfo, ModuleInfoQuery, MachineSnapshot, spawn_machine },
+    game::blueprint::{ self, Blueprints, BlueprintTag },
+    modules::{ ModuleComponent, ModuleConfigChanged, config::{ self, ParamValue } },
+    query::QueryQuerySimple,
+};
+
+use super::{
+    interact::{ InteractiveRotation, RotateModule },
+    intersect::{ MoveType, RequestedMove },
+};
+
+/// how many things you can undo
+const MAX_HISTORY: usize = 256;
+
+/// something the player just did in the editor, sent by whatever did it so it can be undone
+pub struct RecordEdit(pub Edit);
+
+/// an edit as its happening, the entities are still around so we can take a snapshot of them
+pub enum Edit {
+    /// moved from `from` to wherever it is now
+    Move { entity: Entity, from: Vec3 },
+    /// rotated from `before` to whatever it is now
+    Rotate { entity: Entity, before: InteractiveRotation },
+    /// a piece turned from `from` to however its turned now
+    Turn { entity: Entity, from: Quat },
+    /// placed down after being spawned
+    Spawn { entity: Entity },
+    /// about to be despawned
+    Delete { entity: Entity },
+    /// everything got replaced with a save
+    Load { before: MachineSnapshot, after: MachineSnapshot },
+    /// some modules got despawned and others spawned in their place
+    Replace { before: Vec<(EditorId, ModuleInfo)>, after: Vec<(EditorId, ModuleInfo)> },
+    /// a module became part of a different blueprint, or stopped being part of one
+    Retag { entity: Entity, before: Option<BlueprintTag>, after: Option<BlueprintTag> },
+    /// a blueprint was saved over
+    Blueprint {
+        name: String,
+        before: Option<blueprint::Blueprint>,
+        after: Option<blueprint::Blueprint>,
+    },
+    /// a bunch of edits to a group of modules that get undone all at once
+    Group(Vec<Edit>),
+}
+
+/// an edit that can be done and undone, refers to modules by their [`EditorId`] since the
+/// entities come and go
+#[derive(Clone)]
+enum Command {
+    Move { id: EditorId, from: Vec3, to: Vec3 },
+    Rotate { id: EditorId, before: InteractiveRotation, after: InteractiveRotation },
+    Turn { id: EditorId, from: Quat, to: Quat },
+    Spawn { id: EditorId, info: ModuleInfo },
+    Delete { id: EditorId, info: ModuleInfo },
+    SpawnPiece { id: EditorId, info: PieceInfo },
+    DeletePiece { id: EditorId, info: PieceInfo },
+    /// only the settings, so undoing one doesnt also undo whatever the module did since
+    Config { id: EditorId, before: Vec<ParamValue>, after: Vec<ParamValue> },
+    Load { before: MachineSnapshot, after: MachineSnapshot },
+    Retag { id: EditorId, before: Option<BlueprintTag>, after: Option<BlueprintTag> },
+    Blueprint {
+        name: String,
+        before: Option<blueprint::Blueprint>,
+        after: Option<blueprint::Blueprint>,
+    },
+    Group(Vec<Command>),
+}
+
+impl Command {
+    /// the command that undoes this one
+    fn inverse(self) -> Self {
+        use Command::*;
+        match self {
+            Move { id, from, to } => Move { id, from: to, to: from },
+            Rotate { id, before, after } => Rotate { id, before: after, after: before },
+            Turn { id, from, to } => Turn { id, from: to, to: from },
+            Spawn { id, info } => Delete { id, info },
+            Delete { id, info } => Spawn { id, info },
+            SpawnPiece { id, info } => DeletePiece { id, info },
+            DeletePiece { id, info } => SpawnPiece { id, info },
+            Config { id, before, after } => Config { id, before: after, after: before },
+            Load { before, after } => Load { before: after, after: before },
+            Retag { id, before, after } => Retag { id, before: after, after: before },
+            Blueprint { name, before, after } => Blueprint { name, before: after, after: before },
+            Group(commands) => Group(commands.into_iter().rev().map(Command::inverse).collect()),
+        }
+    }
+
+    /// the module this command is about, if its only about one
+    fn id(&self) -> Option<EditorId> {
+        use Command::*;
+        match self {
+            | Move { id, .. }
+            | Rotate { id, .. }
+            | Turn { id, .. }
+            | Spawn { id, .. }
+            | Delete { id, .. }
+            | SpawnPiece { id, .. }
+            | DeletePiece { id, .. }
+            | Config { id, .. }
+            | Retag { id, .. } => Some(*id),
+            Load { .. } | Blueprint { .. } | Group(_) => None,
+        }
+    }
+}
+
+/// the undo and redo stacks
+#[derive(Resource, Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+    /// the module whose config was changed last frame, dragging a value around should only make
+    /// one undo step
+    merging: Option<EditorId>,
+}
+
+impl History {
+    fn push(&mut self, command: Command) {
+        self.redo.clear();
+        self.undo.push(command);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+    }
+
+    /// forget everything, like when switching levels
+    pub fn clear(&mut self) {
+        *self = default();
+    }
+}
+
+/// everything needed to turn an [`Edit`] into a [`Command`]
+#[derive(SystemParam)]
+pub struct EditSnapshot<'w, 's> {
+    q_id: Query<'w, 's, &'static EditorId>,
+    q_info: ModuleInfoQuery<'w, 's>,
+    q_transform: Query<'w, 's, &'static Transform>,
+    q_interactive_rot: Query<'w, 's, &'static InteractiveRotation>,
+    q_module_state: Query<'w, 's, &'static ModuleState>,
+    q_piece: Query<'w, 's, &'static PieceKind>,
+}
+
+impl<'w, 's> EditSnapshot<'w, 's> {
+    fn command(&self, edit: &Edit) -> Option<Command> {
+        let command = match edit {
+            &Edit::Move { entity, from } => {
+                let id = *self.q_id.get(entity).ok()?;
+                Command::Move { id, from, to: self.q_transform.get(entity).ok()?.translation }
+            }
+            Edit::Rotate { entity, before } => {
+                let id = *self.q_id.get(*entity).ok()?;
+                // if the widgets are up thats the most up to date, otherwise read it off the module
+                let after = match self.q_interactive_rot.get(*entity) {
+                    Ok(after) => after.clone(),
+                    Err(_) =>
+                        InteractiveRotation::of(self.q_module_state.get(*entity).ok()?, |e|
+                            self.q_transform.get(*e).ok()
+                        )?,
+                };
+                if after == *before {
+                    return None;
+                }
+                Command::Rotate { id, before: before.clone(), after }
+            }
+            &Edit::Turn { entity, from } => {
+                let id = *self.q_id.get(entity).ok()?;
+                Command::Turn { id, from, to: self.q_transform.get(entity).ok()?.rotation }
+            }
+            &Edit::Spawn { entity } => {
+                let id = *self.q_id.get(entity).ok()?;
+                match self.piece(entity) {
+                    Some(info) => Command::SpawnPiece { id, info },
+                    None => Command::Spawn { id, info: self.q_info.get(entity)? },
+                }
+            }
+            &Edit::Delete { entity } => {
+                let id = *self.q_id.get(entity).ok()?;
+                match self.piece(entity) {
+                    Some(info) => Command::DeletePiece { id, info },
+                    None => Command::Delete { id, info: self.q_info.get(entity)? },
+                }
+            }
+            Edit::Load { before, after } => {
+                Command::Load { before: before.clone(), after: after.clone() }
+            }
+            Edit::Replace { before, after } => {
+                let deletes = before
+                    .iter()
+                    .map(|(id, info)| Command::Delete { id: *id, info: info.clone() });
+                let spawns = after
+                    .iter()
+                    .map(|(id, info)| Command::Spawn { id: *id, info: info.clone() });
+                Command::Group(deletes.chain(spawns).collect())
+            }
+            Edit::Retag { entity, before, after } => {
+                let id = *self.q_id.get(*entity).ok()?;
+                Command::Retag { id, before: before.clone(), after: after.clone() }
+            }
+            Edit::Blueprint { name, before, after } => {
+                Command::Blueprint { name: name.clone(), before: before.clone(), after: after.clone() }
+            }
+            Edit::Group(edits) => {
+                // groups only go one deep, so any inside of this one get flattened into it
+                let commands = edits
+                    .iter()
+                    .filter_map(|edit| self.command(edit))
+                    .flat_map(|command| match command {
+                        Command::Group(commands) => commands,
+                        command => vec![command],
+                    })
+                    .collect::<Vec<_>>();
+                if commands.is_empty() {
+                    return None;
+                }
+                Command::Group(commands)
+            }
+        };
+        Some(command)
+    }
+
+    fn piece(&self, entity: Entity) -> Option<PieceInfo> {
+        Some(PieceInfo::of(*self.q_piece.get(entity).ok()?, self.q_transform.get(entity).ok()?))
+    }
+}
+
+/// turn [`RecordEdit`]s and [`ModuleConfigChanged`]s into commands on the undo stack
+pub fn record_edits(
+    mut edits: EventReader<RecordEdit>,
+    mut config_events: EventReader<ModuleConfigChanged>,
+    mut history: ResMut<History>,
+    q_id: Query<&EditorId>,
+    snapshot: EditSnapshot,
+) {
+    for RecordEdit(edit) in edits.iter() {
+        if let Some(command) = snapshot.command(edit) {
+            history.push(command);
+        }
+    }
+
+    let mut merging = None;
+    for ModuleConfigChanged { entity, before, after } in config_events.iter() {
+        let Ok(&id) = q_id.get(*entity) else {
+            continue;
+        };
+        let merge = history.merging == Some(id) &&
+            matches!(history.undo.last(), Some(Command::Config { id: last, .. }) if *last == id);
+        if merge && let Some(Command::Config { after: last_after, .. }) = history.undo.last_mut() {
+            *last_after = after.clone();
+        } else {
+            history.push(Command::Config { id, before: before.clone(), after: after.clone() });
+        }
+        merging = Some(id);
+    }
+    history.merging = merging;
+}
+
+/// ctrl+z / ctrl+shift+z (or ctrl+y)
+#[allow(clippy::too_many_arguments)]
+pub fn undo_redo(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut history: ResMut<History>,
+    mut selected: ResMut<SelectedModules>,
+    q_id: Query<(Entity, &EditorId)>,
+    q_machine: Query<Entity, (Or<(With<marker::Module>, With<PieceKind>)>, Without<marker::Locked>)>,
+    mut q_module: Query<&mut ModuleComponent>,
+    mut q_interactive_rot: Query<&mut InteractiveRotation>,
+    mut rotate: RotateModule,
+    mut requested_move: EventWriter<RequestedMove>,
+    mut spawn_events: EventWriter<SpawnModule>,
+    mut spawn_pieces: EventWriter<SpawnPiece>,
+    mut blueprints: ResMut<Blueprints>,
+) {
+    // let text boxes have their own undo
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let ctrl = keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if !ctrl {
+        return;
+    }
+
+    let command = if keyboard.just_pressed(KeyCode::Z) && !shift {
+        let Some(command) = history.undo.pop() else {
+            return;
+        };
+        history.redo.push(command.clone());
+        command.inverse()
+    } else if keyboard.just_pressed(KeyCode::Y) || (keyboard.just_pressed(KeyCode::Z) && shift) {
+        let Some(command) = history.redo.pop() else {
+            return;
+        };
+        history.undo.push(command.clone());
+        command
+    } else {
+        return;
+    };
+    history.merging = None;
+
+    let find = |id: EditorId| q_id.iter().find(|(_, e_id)| **e_id == id).map(|(e, _)| e);
+
+    let commands_to_apply = match command {
+        Command::Group(group) => group,
+        command => vec![command],
+    };
+    // modules in the same group dont get in each others way while theyre being put back
+    let group = commands_to_apply
+        .iter()
+        .filter_map(Command::id)
+        .filter_map(find)
+        .collect::<HashSet<_>>();
+
+    use Command::*;
+    for command in commands_to_apply {
+        match command {
+            Move { id, to, .. } => {
+                let Some(entity) = find(id) else {
+                    continue;
+                };
+                // still has to go through collision checking like any other move
+                requested_move.send(
+                    RequestedMove::new(entity, MoveType::TranslateTo(to)).ignore(group.clone())
+                );
+            }
+            Rotate { id, after, .. } => {
+                let Some(entity) = find(id) else {
+                    continue;
+                };
+                // if its selected the widgets need to know about it too
+                if let Ok(mut i_rot) = q_interactive_rot.get_mut(entity) {
+                    *i_rot = after;
+                } else {
+                    rotate.apply(entity, &after);
+                }
+            }
+            Turn { id, to, .. } => {
+                let Some(entity) = find(id) else {
+                    continue;
+                };
+                requested_move.send(
+                    RequestedMove::new(entity, MoveType::RotateTo(to)).ignore(group.clone())
+                );
+            }
+            Spawn { id, info } => {
+                spawn_events.send(SpawnModule::new(info).with_id(id));
+            }
+            SpawnPiece { id, info } => {
+                spawn_pieces.send(SpawnPiece::new(info).with_id(id));
+            }
+            Delete { id, .. } | DeletePiece { id, .. } => {
+                let Some(entity) = find(id) else {
+                    continue;
+                };
+                selected.remove(entity);
+                commands.entity(entity).despawn_recursive();
+            }
+            Config { id, after, .. } => {
+                let Some(entity) = find(id) else {
+                    continue;
+                };
+                config::set_param_values(&mut *q_module.entity_mut(entity).module, &after);
+            }
+            Load { after, .. } => {
+                selected.clear_selected();
+                for entity in q_machine.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_machine(&after, &mut spawn_events, &mut spawn_pieces);
+            }
+            Retag { id, after, .. } => {
+                let Some(entity) = find(id) else {
+                    continue;
+                };
+                match after {
+                    Some(tag) => commands.entity(entity).insert(tag),
+                    None => commands.entity(entity).remove::<BlueprintTag>(),
+                };
+            }
+            Blueprint { name, after, .. } => {
+                // only in memory, the file stays how it was last saved until its saved again
+                blueprint::set_blueprint(&mut blueprints, &name, after);
+            }
+            Group(group) => {
+                error!("groups of groups dont happen, skipping {} commands", group.len());
+            }
+        }
+    }
+}
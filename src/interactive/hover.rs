@@ -2,7 +2,7 @@ use std::f32::consts::PI;
 
 use bevy_prototype_lyon::shapes::Circle;
 
-use crate::*;
+use crate::{ *, modules::ModuleComponent };
 
 use super::{
     interact::{Interactive, InteractiveSelected},
@@ -126,3 +126,82 @@ pub fn draw_selection_on_hovered(
         }
     }
 }
+
+/// put a ring around everything in the selected group so you can tell whats gonna move with the
+/// main one
+pub fn draw_group_outlines(
+    mut commands: Commands,
+    selected: Res<SelectedModules>,
+    q_module: Query<&ModuleComponent>,
+    mut outlines: Local<Vec<Entity>>,
+) {
+    if !selected.is_changed() {
+        return;
+    }
+    for e in outlines.drain(..) {
+        if let Some(commands) = commands.get_entity(e) {
+            commands.despawn_recursive();
+        }
+    }
+    if selected.place {
+        return;
+    }
+
+    for entity in selected.group.iter().copied() {
+        let Ok(module) = q_module.get(entity) else {
+            continue;
+        };
+        let mut stroke = StrokeMode::new(Color::rgba(1.0, 1.0, 1.0, 0.4), 0.5);
+        stroke.options.tolerance = 0.01;
+        let circle = Circle {
+            center: Vec2::ZERO,
+            radius: module.ty.spawn_instructions().body.offset() + 2.0,
+        };
+        let transform = Transform::from_translation(Vec3::Z * ZOrder::HoverIndicator.f32());
+        let e = commands
+            .spawn(GeometryBuilder::build_as(&circle, DrawMode::Stroke(stroke), transform))
+            .name("group.sprite")
+            .id();
+        commands.entity(entity).add_child(e);
+        outlines.push(e);
+    }
+}
+
+/// draw a faint line between a selected teleporter and whatever its linked to
+pub fn draw_links(
+    mut commands: Commands,
+    selected: Res<SelectedModules>,
+    q_module: Query<(Entity, &ModuleComponent, &Transform)>,
+    mut lines: Local<Vec<Entity>>,
+) {
+    for e in lines.drain(..) {
+        commands.entity(e).despawn();
+    }
+    if selected.place {
+        return;
+    }
+    let Some((entity, link, from)) = selected.selected
+        .and_then(|e| q_module.get(e).ok())
+        .and_then(|(e, module, tf)| Some((e, module.module.link()?, tf.translation.truncate()))) else {
+        return;
+    };
+
+    let mut stroke = StrokeMode::new(Color::rgba(1.0, 1.0, 1.0, 0.25), 0.5);
+    stroke.options.tolerance = 0.01;
+    for (_, _, transform) in q_module
+        .iter()
+        .filter(|(e, module, _)| *e != entity && module.module.link().map_or(false, |l| l.pairs_with(link))) {
+        let line = shapes::Line(from, transform.translation.truncate());
+        let e = commands
+            .spawn(
+                GeometryBuilder::build_as(
+                    &line,
+                    DrawMode::Stroke(stroke),
+                    Transform::from_translation(Vec3::Z * ZOrder::Tracer.f32())
+                )
+            )
+            .name("link.sprite")
+            .id();
+        lines.push(e);
+    }
+}
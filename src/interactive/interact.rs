@@ -1,10 +1,12 @@
 use atlas::{ basic, AtlasDictionary };
+use bevy::ecs::system::SystemParam;
 use std::{ collections::hash_map::DefaultHasher, f32::consts::PI, hash::{ Hash, Hasher } };
 
 use crate::{
-    modules::{ModuleType, ModuleComponent},
+    modules::{ ModuleType, ModuleComponent, body::BodyType },
     query::{ QueryQueryIter, QueryQuerySimple },
     select::CursorCoords,
+    history::{ RecordEdit, Edit },
     *, engine::module_state::ModuleState,
 };
 
@@ -15,7 +17,7 @@ pub enum Interactive {
     Delete,
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, PartialEq)]
 pub struct InteractiveRotation {
     pub input_rot: Vec<f32>,
     pub output_rot: Vec<f32>,
@@ -23,13 +25,26 @@ pub struct InteractiveRotation {
 }
 
 impl InteractiveRotation {
+    /// port rotations are stored relative to the body
     pub fn from<'a, T: Iterator<Item = &'a Transform>>(inputs: T, outputs: T, rot: &'a Transform) -> Self {
+        let rot = rot.rotation.to_euler(EulerRot::XYZ).2;
         Self {
-            input_rot: inputs.map(|t| t.rotation.to_euler(EulerRot::XYZ).2).collect(),
-            output_rot: outputs.map(|t| t.rotation.to_euler(EulerRot::XYZ).2).collect(),
-            rot: rot.rotation.to_euler(EulerRot::XYZ).2,
+            input_rot: inputs.map(|t| t.rotation.to_euler(EulerRot::XYZ).2 - rot).collect(),
+            output_rot: outputs.map(|t| t.rotation.to_euler(EulerRot::XYZ).2 - rot).collect(),
+            rot,
         }
     }
+
+    /// read the rotation off of a module's body and ports as they are right now
+    pub fn of<'a>(state: &ModuleState, get: impl Fn(&Entity) -> Option<&'a Transform>) -> Option<Self> {
+        Some(
+            Self::from(
+                state.inputs.iter().filter_map(&get),
+                state.outputs.iter().filter_map(&get),
+                get(&state.body)?
+            )
+        )
+    }
 }
 
 const ROTATION_WIDGET_OFFSET: f32 = 4.0;
@@ -46,6 +61,7 @@ pub fn spawn_despawn_interactive_components(
     mut q_module: Query<&mut ModuleComponent>,
     has_interactive: Query<With<Interactive>>,
     q_module_state: Query<&ModuleState>,
+    has_locked: Query<With<marker::Locked>>,
     mut before: Local<Option<Entity>>
 ) {
     // only run when SelectedModules is changed but not when its been added
@@ -63,7 +79,8 @@ pub fn spawn_despawn_interactive_components(
     *prev_selected = hash;
 
     // spawn all the interactive components
-    if let Some(module) = selected.selected {
+    // pieces dont get any widgets
+    if let Some(module) = selected.selected.filter(|e| q_module_state.get(*e).is_ok()) {
         if let Some(b) = *before {
             let to_be_removed: Vec<_> = q_children
                 .iter_descendants(b)
@@ -79,7 +96,7 @@ pub fn spawn_despawn_interactive_components(
 
         *before = Some(module);
 
-        let body = &q_module.entity_mut(module).ty.spawn_instructions().body;
+        let body = q_module.entity_mut(module).ty.spawn_instructions().body;
 
         macro spawn_widget(
             $translation:expr,
@@ -114,9 +131,11 @@ pub fn spawn_despawn_interactive_components(
 
         let mut children = vec![];
 
+        let body_rot = q_transform.entity(state.body).rotation.to_euler(EulerRot::XYZ).2;
         for entity in state.inputs.iter().chain(state.outputs.iter()) {
+            let rel = q_transform.entity(*entity).rotation.to_euler(EulerRot::XYZ).2 - body_rot;
             let child = spawn_widget!(
-                Vec3::X * (ROTATION_WIDGET_OFFSET + body.offset()),
+                Vec3::X * (ROTATION_WIDGET_OFFSET + body.offset_at(rel)),
                 color,
                 "rotation.widget",
                 2.0,
@@ -124,18 +143,21 @@ pub fn spawn_despawn_interactive_components(
             );
             commands.entity(*entity).add_child(child);
         }
-        children.push(
-            spawn_widget!(
-                Vec3::new(body.offset() - 3.0, 0.0, 0.0),
-                color,
-                "io_rotation.widget",
-                1.0,
-                Interactive::IORotation
-            )
-        );
-        children.push(
-            spawn_widget!(Vec3::ZERO, Color::RED, "delete.widget", 1.0, Interactive::Delete)
-        );
+        // locked modules only get to aim their ports
+        if !has_locked.has(module) {
+            children.push(
+                spawn_widget!(
+                    Vec3::new(body.offset() - 3.0, 0.0, 0.0),
+                    color,
+                    "io_rotation.widget",
+                    1.0,
+                    Interactive::IORotation
+                )
+            );
+            children.push(
+                spawn_widget!(Vec3::ZERO, Color::RED, "delete.widget", 1.0, Interactive::Delete)
+            );
+        }
 
         commands.entity(module).push_children(&children);
 
@@ -189,20 +211,28 @@ pub fn use_widgets(
     mouse_pos: Res<CursorCoords>,
     mut diff: Local<Option<f32>>,
     keyboard: Res<Input<KeyCode>>,
-    tracers: Res<tracer::TracerEntities>,
-    mut q_visibility: Query<&mut Visibility>
+    mut record: EventWriter<RecordEdit>,
+    mut rot_before: Local<Option<(Entity, InteractiveRotation)>>,
+    has_locked: Query<With<marker::Locked>>
 ) {
+    // once we let go, whatever rotating we did becomes one undo step
+    let mut finish = |active: &mut bool, diff: &mut Option<f32>| {
+        *active = false;
+        *diff = None;
+        if let Some((entity, before)) = rot_before.take() {
+            record.send(RecordEdit(Edit::Rotate { entity, before }));
+        }
+    };
+
     // uh just trust me this works
     // i kinda forgot the logic behind it like right after i wrote it
     let changed = interactive_selected.is_changed();
     if (!changed && !*active) || !buttons.pressed(MouseButton::Left) {
-        *active = false;
-        *diff = None;
+        finish(&mut *active, &mut *diff);
         return;
     }
     let Some(entity) = **interactive_selected else {
-        *active = false;
-        *diff = None;
+        finish(&mut *active, &mut *diff);
         return;
     };
     *active = true;
@@ -224,6 +254,9 @@ pub fn use_widgets(
             let module = q_parent.entity(io_port).get();
 
             let mut i_rot = q_interactive_rot.entity_mut(module);
+            if diff.is_none() {
+                *rot_before = Some((module, i_rot.clone()));
+            }
             let rot = i_rot.rot;
             // dbg!(&i_rot);
             let io_rot = if let Ok(marker::Input(n)) = q_in.get(io_port) {
@@ -254,6 +287,9 @@ pub fn use_widgets(
         IORotation => {
             let module = q_parent.entity(entity).get();
             let mut i_rot = q_interactive_rot.entity_mut(module);
+            if diff.is_none() {
+                *rot_before = Some((module, i_rot.clone()));
+            }
 
             let root = q_transform.entity(module);
             let Some(angle) = rel_angle(root) else {
@@ -271,14 +307,19 @@ pub fn use_widgets(
             }
         }
         Delete => {
+            // the whole selection goes, not just the one with the widget on it
             let parent = q_parent.entity(entity).get();
-            commands.entity(parent).despawn_recursive();
+            let doomed = std::iter::once(parent)
+                .chain(selected.all().filter(|e| *e != parent && !has_locked.has(*e)))
+                .collect::<Vec<_>>();
+            record.send(
+                RecordEdit(Edit::Group(doomed.iter().map(|&entity| Edit::Delete { entity }).collect()))
+            );
+            for e in doomed {
+                commands.entity(e).despawn_recursive();
+            }
             *active = false;
             selected.clear_selected();
-
-            for tracer in **tracers {
-                *q_visibility.entity_mut(tracer) = Visibility::INVISIBLE;
-            }
         }
     }
 }
@@ -287,9 +328,7 @@ pub fn do_interactive_rotation(
     w_interactive_rot: Query<Entity, Changed<InteractiveRotation>>,
     q_interactive_rot: Query<&InteractiveRotation>,
     q_interactive: Query<&Interactive>,
-    mut q_transform: Query<&mut Transform>,
-    q_module_state: Query<&ModuleState>,
-    q_children: Query<&Children>,
+    mut rotate: RotateModule,
 ) {
     let Ok(entity) = w_interactive_rot.get_single() else {
         return;
@@ -297,7 +336,7 @@ pub fn do_interactive_rotation(
     let Ok(i_rot) = q_interactive_rot.get(entity) else {
         return;
     };
-    let children = q_children.entity(entity);
+    let children = rotate.q_children.entity(entity);
 
     let interactive = children.iter().filter_map(|e|
         q_interactive
@@ -309,7 +348,7 @@ pub fn do_interactive_rotation(
         use Interactive::*;
         match i {
             IORotation => {
-                let mut transform = q_transform.entity_mut(*e);
+                let mut transform = rotate.q_transform.entity_mut(*e);
                 let z = transform.translation.z;
                 let rot = transform.rotation.to_euler(EulerRot::XYZ).2;
 
@@ -324,27 +363,44 @@ pub fn do_interactive_rotation(
             }
         }
     }
-    
-    let state = q_module_state.entity(entity);
-
-    for (i, input) in state.inputs.iter().enumerate() {
-        let mut transform = q_transform.entity_mut(*input);
-        transform.rotation = Quat::from_euler(
-            EulerRot::XYZ,
-            0.0,
-            0.0,
-            i_rot.input_rot[i] + i_rot.rot
-        );
-    }
-    for (i, output) in state.outputs.iter().enumerate() {
-        let mut transform = q_transform.entity_mut(*output);
-        transform.rotation = Quat::from_euler(
-            EulerRot::XYZ,
-            0.0,
-            0.0,
-            i_rot.output_rot[i] + i_rot.rot
-        );
+
+    rotate.apply(entity, i_rot);
+}
+
+/// everything needed to turn a module's body and ports to match an [`InteractiveRotation`]
+#[derive(SystemParam)]
+pub struct RotateModule<'w, 's> {
+    q_transform: Query<'w, 's, &'static mut Transform>,
+    q_module_state: Query<'w, 's, &'static ModuleState>,
+    q_children: Query<'w, 's, &'static Children>,
+    q_body: Query<'w, 's, &'static BodyType>,
+}
+
+impl<'w, 's> RotateModule<'w, 's> {
+    pub fn apply(&mut self, entity: Entity, i_rot: &InteractiveRotation) {
+        let q_transform = &mut self.q_transform;
+        let state = self.q_module_state.entity(entity);
+        let body = *self.q_body.entity(state.body);
+        let body_rot = q_transform.entity(state.body).rotation.to_euler(EulerRot::XYZ).2;
+
+        let ports = state.inputs
+            .iter()
+            .zip(i_rot.input_rot.iter())
+            .chain(state.outputs.iter().zip(i_rot.output_rot.iter()));
+        for (&port, &rel) in ports {
+            let mut transform = q_transform.entity_mut(port);
+            let old_rel = transform.rotation.to_euler(EulerRot::XYZ).2 - body_rot;
+            transform.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, rel + i_rot.rot);
+
+            // the edge isnt the same distance away all the way around if the body isnt round
+            let reach = body.offset_at(rel) - body.offset_at(old_rel);
+            if reach.abs() > f32::EPSILON {
+                for child in self.q_children.entity(port).iter() {
+                    q_transform.entity_mut(*child).translation.x += reach;
+                }
+            }
+        }
+        let mut transform = q_transform.entity_mut(state.body);
+        transform.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, i_rot.rot);
     }
-    let mut transform = q_transform.entity_mut(state.body);
-    transform.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, i_rot.rot);
-}
\ No newline at end of file
+}
@@ -1,6 +1,13 @@
 // use bevy_prototype_debug_lines::DebugLines;
 
-use crate::{misc::RapierContextMethods, query::QueryQuerySimple, *};
+use crate::{
+    misc::RapierContextMethods,
+    query::QueryQuerySimple,
+    *,
+    engine::module_state::ModuleState,
+};
+
+use super::interact::InteractiveRotation;
 
 pub enum MoveType {
     TranslateTo(Vec3),
@@ -9,9 +16,14 @@ pub enum MoveType {
 
 pub struct RequestedMove {
     pub requesting: Entity,
+    /// modules whose colliders dont count as being in the way
     pub ignore: HashSet<Entity>,
+    /// modules that get moved right along with `requesting`
+    pub followers: Vec<Entity>,
     pub move_type: MoveType,
     pub snap_flag: bool,
+    /// if it gets stopped short, snap to this grid instead of the half-pixel one
+    pub grid: Option<f32>,
 }
 
 impl RequestedMove {
@@ -19,8 +31,10 @@ impl RequestedMove {
         Self {
             requesting,
             ignore: default(),
+            followers: vec![],
             move_type,
             snap_flag: false,
+            grid: None,
         }
     }
 
@@ -29,10 +43,22 @@ impl RequestedMove {
         self
     }
 
-    // pub fn ignore(mut self, ignore: HashSet<Entity>) -> Self {
-    //     self.ignore = ignore;
-    //     self
-    // }
+    /// snap to a grid of this size, for pieces
+    pub fn grid(mut self, size: f32) -> Self {
+        self.snap_flag = true;
+        self.grid = Some(size);
+        self
+    }
+
+    pub fn ignore(mut self, ignore: HashSet<Entity>) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    pub fn with_followers(mut self, followers: Vec<Entity>) -> Self {
+        self.followers = followers;
+        self
+    }
 
     // pub fn to_transform(&self) -> Transform {
     //     use MoveType::*;
@@ -50,16 +76,27 @@ pub fn do_requested_move(
     q_collider: Query<(Entity, &Collider)>,
     has_rigidbody: Query<With<RigidBody>>,
     q_global_transform: Query<&GlobalTransform>,
+    q_module_state: Query<&ModuleState>,
+    mut q_interactive_rot: Query<&mut InteractiveRotation>,
     rapier_ctx: Res<RapierContext>, // mut lines: ResMut<DebugLines>,
 ) {
     use MoveType::*;
 
     for requested_move in requested_moves.iter() {
-        let mut colliders = q_children
-            .iter_descendants(requested_move.requesting)
+        let movers = std::iter::once(requested_move.requesting)
+            .chain(requested_move.followers.iter().copied())
+            .collect::<Vec<_>>();
+        let mut colliders = movers
+            .iter()
+            .flat_map(|e| q_children.iter_descendants(*e))
             .filter_map(|e| q_collider.get(e).ok())
             .collect::<Vec<_>>();
-        let ignore = colliders.iter().map(|(e, _)| *e).collect::<Vec<_>>();
+        let mut ignore = colliders.iter().map(|(e, _)| *e).collect::<Vec<_>>();
+        ignore.extend(
+            requested_move.ignore
+                .iter()
+                .flat_map(|e| q_children.iter_descendants(*e))
+        );
         colliders.retain(|(e, _)| has_rigidbody.get(*e).is_ok());
 
         let predicate = |e| !ignore.contains(&e) && has_rigidbody.get(e).is_ok();
@@ -67,8 +104,11 @@ pub fn do_requested_move(
             .exclude_sensors()
             .predicate(&predicate);
 
-        let requesting = q_transform.entity(requested_move.requesting);
-        let mut diff = *requesting;
+        let Ok(&requesting) = q_transform.get(requested_move.requesting) else {
+            // error!("Could not find transform component on requested_move.requesting, also this shouldnt have hapenned")
+            continue;
+        };
+        let mut diff = requesting;
 
         match requested_move.move_type {
             TranslateTo(to) => {
@@ -82,7 +122,9 @@ pub fn do_requested_move(
         // move this thingy
         let transform = |factor: f32, transform: Transform| match requested_move.move_type {
             TranslateTo(_) => {
-                Transform::from_translation(transform.translation - diff.translation * factor)
+                let mut cpy = transform;
+                cpy.translation -= diff.translation * factor;
+                cpy
             }
             RotateTo(_) => {
                 let mut cpy = transform;
@@ -92,56 +134,118 @@ pub fn do_requested_move(
         };
 
         // this tests every collider to see if any of then satisfy the
-        let test = |factor: f32| {
+        // `shift` nudges them on top of that, for testing a snapped position
+        let test_shifted = |factor: f32, shift: Vec3| {
             colliders
                 .iter()
                 .map(|(e, c)| {
-                    rapier_ctx.intersection_with_shape_transform(
-                        transform(factor, q_global_transform.entity(*e).compute_transform()),
-                        c,
-                        filter,
-                    )
+                    let mut at = transform(factor, q_global_transform.entity(*e).compute_transform());
+                    at.translation += shift;
+                    rapier_ctx.intersection_with_shape_transform(at, c, filter)
                 })
                 .any(|x| x.is_some())
         };
+        let test = |factor: f32| test_shifted(factor, Vec3::ZERO);
 
+        let mut out = requesting;
         // let mut factor = 0.5;
         if test(1.0) {
+            // a turn either fits or it doesnt, theres no stopping partway around
+            if let RotateTo(_) = requested_move.move_type {
+                continue;
+            }
             const N: f32 = 64.0;
-            for i in 0..N as i32 {
-                let factor = 1.0 - (i as f32) / N;
-                if !test(factor) && let Ok(tf) = q_transform.get(requested_move.requesting) {
-                    let mut out = (
-                        unsafe {
-                            q_transform.get_unchecked(requested_move.requesting)
-                        }
-                    ).unwrap();
-
-                    *out = transform(factor - 1.0 / N, *tf);
-                    if requested_move.snap_flag {
-                        let pos = &mut out.translation;
-                        pos.x = (pos.x - 0.5).round() + 0.5;
-                        pos.y = (pos.y - 0.5).round() + 0.5;
-                    }
-
-                    return;
+            let Some(factor) = (0..N as i32)
+                .map(|i| 1.0 - (i as f32) / N)
+                .find(|factor| !test(*factor)) else {
+                continue;
+            };
+
+            let factor = factor - 1.0 / N;
+            out = transform(factor, requesting);
+
+            // snap to the nearest grid point, unless thats inside whatever we ran into, then take
+            // the one on the side we came from. if even that doesnt fit just stay off grid
+            let grid = match (requested_move.grid, requested_move.snap_flag) {
+                (Some(grid), _) => Some((grid, 0.0)),
+                (None, true) => Some((1.0, 0.5)),
+                (None, false) => None,
+            };
+            if let Some((grid, offset)) = grid {
+                let backed_off = out.translation;
+                let start = requesting.translation;
+                let snap = |v: f32, to: fn(f32) -> f32| to((v - offset) / grid) * grid + offset;
+                let toward = |v: f32, start: f32| if start < v {
+                    snap(v, f32::floor)
+                } else {
+                    snap(v, f32::ceil)
+                };
+                let nearest = Vec3::new(
+                    snap(backed_off.x, f32::round),
+                    snap(backed_off.y, f32::round),
+                    backed_off.z
+                );
+                let behind = Vec3::new(
+                    toward(backed_off.x, start.x),
+                    toward(backed_off.y, start.y),
+                    backed_off.z
+                );
+                if let Some(pos) = [nearest, behind]
+                    .into_iter()
+                    .find(|pos| !test_shifted(factor, *pos - backed_off))
+                {
+                    out.translation = pos;
                 }
             }
-            return;
-        }
-
-        if let Ok(mut transform) = q_transform.get_mut(requested_move.requesting) {
+        } else {
             // were good
             match requested_move.move_type {
                 TranslateTo(to) => {
-                    transform.translation = to;
+                    out.translation = to;
                 }
                 RotateTo(to) => {
-                    transform.rotation = to;
+                    out.rotation = to;
+                }
+            }
+        }
+
+        // everything else goes the same way the requesting module did
+        match requested_move.move_type {
+            TranslateTo(_) => {
+                let delta = out.translation - requesting.translation;
+                *q_transform.entity_mut(requested_move.requesting) = out;
+                for &follower in requested_move.followers.iter() {
+                    if let Ok(mut transform) = q_transform.get_mut(follower) {
+                        transform.translation += delta;
+                    }
+                }
+            }
+            RotateTo(_) => {
+                let around = requesting.translation;
+                for &mover in movers.iter() {
+                    let Ok(mut transform) = q_transform.get_mut(mover) else {
+                        continue;
+                    };
+                    let Ok(state) = q_module_state.get(mover) else {
+                        transform.rotate_around(around, diff.rotation);
+                        continue;
+                    };
+                    // modules never turn themselves, just their body and ports
+                    transform.translation = around + diff.rotation * (transform.translation - around);
+                    let parts = std::iter::once(&state.body)
+                        .chain(state.inputs.iter())
+                        .chain(state.outputs.iter());
+                    for &part in parts {
+                        if let Ok(mut transform) = q_transform.get_mut(part) {
+                            transform.rotation = diff.rotation * transform.rotation;
+                        }
+                    }
+                    // and the widgets need to know about it if its selected
+                    if let Ok(mut i_rot) = q_interactive_rot.get_mut(mover) {
+                        i_rot.rot += diff.rotation.to_euler(EulerRot::XYZ).2;
+                    }
                 }
             }
-        } else {
-            // error!("Could not find transform component on requested_move.requesting, also this shouldnt have hapenned")
         }
     }
 }
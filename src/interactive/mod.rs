@@ -3,7 +3,9 @@ use std::collections::VecDeque;
 use crate::*;
 use iyes_loopless::prelude::*;
 
+pub mod clipboard;
 pub mod drag;
+pub mod history;
 pub mod hover;
 pub mod interact;
 pub mod intersect;
@@ -18,11 +20,6 @@ fn place(res: Res<SelectedModules>) -> bool {
     res.place && res.selected.is_some()
 }
 
-/// if we've selected something
-fn select(res: Res<SelectedModules>) -> bool {
-    res.selected.is_some()
-}
-
 #[derive(Deref, DerefMut)]
 struct BoolBuf(VecDeque<bool>);
 
@@ -49,13 +46,18 @@ fn egui(
 
 fn init_res(mut commands: Commands) {
     commands.init_resource::<tracer::TracerEntities>();
+    commands.init_resource::<tracer::OverlayTracers>();
 }
 
 pub fn app(app: &mut App) {
     app.add_event::<intersect::RequestedMove>()
+        .add_event::<history::RecordEdit>()
+        .init_resource::<history::History>()
+        .init_resource::<clipboard::Clipboard>()
         .init_resource::<select::CursorCoords>()
         .init_resource::<hover::HoveredEntities>()
         .init_resource::<interact::InteractiveSelected>()
+        .init_resource::<tracer::TrajectoryOverlay>()
         .add_startup_system_to_stage(StartupStage::Startup, init_res);
 
     app.add_system_set_to_stage(
@@ -80,6 +82,20 @@ pub fn app(app: &mut App) {
                     .before("intersect::do_requested_move")
                     .label("select::drag_selected")
             )
+            .with_system(
+                select::box_select
+                    .run_if_not(place)
+                    .run_if_not(egui)
+                    .after("select::get_selected")
+            )
+            .with_system(
+                drag::rotate_group
+                    .run_if_not(place)
+                    .run_if_not(egui)
+                    .after("select::get_selected")
+                    .before("interact::do_interactive_rotation")
+                    .label("drag::rotate_group")
+            )
             .with_system(
                 select::place_selected
                     .run_if(place)
@@ -103,13 +119,36 @@ pub fn app(app: &mut App) {
                     .label("interact::do_interactive_rotation")
             )
             .with_system(intersect::do_requested_move.label("intersect::do_requested_move"))
+            .with_system(
+                history::record_edits
+                    .after("select::drag_selected")
+                    .after("drag::rotate_group")
+                    .after("interact::do_interactive_rotation")
+                    .after("interact::use_widgets")
+                    .after("intersect::do_requested_move")
+                    .label("history::record_edits")
+            )
+            .with_system(
+                history::undo_redo
+                    .run_if_not(place)
+                    .after("history::record_edits")
+            )
+            .with_system(clipboard::clipboard_keys.run_if_not(place).after("history::record_edits"))
+            .with_system(select::delete_selected.run_if_not(place).after("history::record_edits"))
             .with_system(
                 tracer::tracer
-                    .run_if(select)
-                    .run_if(|r: Res<SelectedModules>| !r.place)
                     .after("select::get_selected")
                     .after("select::drag_selected")
+                    .after("intersect::do_requested_move")
+            )
+            .with_system(tracer::toggle_overlay.label("tracer::toggle_overlay"))
+            .with_system(
+                tracer::trajectory_overlay
+                    .after("tracer::toggle_overlay")
+                    .after("intersect::do_requested_move")
             )
             .with_system(hover::draw_selection_on_hovered)
+            .with_system(hover::draw_group_outlines)
+            .with_system(hover::draw_links)
     );
 }
\ No newline at end of file
@@ -1,8 +1,19 @@
 use std::f32::consts::TAU;
 
-use crate::{misc::RapierContextMethods, query::QueryQuerySimple, *, modules::BodyType};
+use crate::{
+    misc::RapierContextMethods,
+    query::QueryQuerySimple,
+    *,
+    modules::BodyType,
+    engine::piece::PieceKind,
+    game::blueprint::BlueprintTag,
+};
 
-use super::{hover::HoveredEntities, intersect::{RequestedMove, MoveType}};
+use super::{
+    history::{ RecordEdit, Edit },
+    hover::HoveredEntities,
+    intersect::{ RequestedMove, MoveType },
+};
 
 /// update SelectedModule whenever the left cursor is clicked
 #[allow(clippy::too_many_arguments)]
@@ -15,7 +26,13 @@ pub fn get_selected(
     has_body: Query<With<BodyType>>,
     has_interactive: Query<With<interact::Interactive>>,
     mut interactive_selected: ResMut<interact::InteractiveSelected>,
+    keyboard: Res<Input<KeyCode>>,
+    has_locked: Query<With<marker::Locked>>,
+    q_blueprint: Query<(Entity, &BlueprintTag)>,
+    has_piece: Query<With<PieceKind>>,
 ) {
+    let shift = keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
     // get that window
     let Some(window) = windows.get_primary_mut() else {
         error!("no window you dingus");
@@ -32,18 +49,38 @@ pub fn get_selected(
             **interactive_selected = Some(e);
         }
     } else if
-    // then check if weve selected a body
-    let Some(&e) = hovered.iter().find(|e| has_body.has(**e)) {
+    // then check if weve selected a body (or a piece)
+    let Some(&e) = hovered.iter().find(|e| {
+        has_body.has(**e) || q_parent.get(**e).map_or(false, |p| has_piece.has(p.get()))
+    }) {
         glow = e;
         // if clicky click, set selected modules
         if buttons.just_pressed(MouseButton::Left) {
-            *selected = SelectedModules::from_entity(q_parent.entity(e).get());
+            let module = q_parent.entity(e).get();
+            if shift {
+                // locked modules dont get to be part of a group
+                if !has_locked.has(module) {
+                    selected.toggle(module);
+                }
+            } else if selected.contains(module) {
+                // keep the group so it can be dragged around together
+                selected.focus(module);
+            } else if let Ok((_, tag)) = q_blueprint.get(module) {
+                // copies of blueprints get picked up as a whole
+                let rest = q_blueprint
+                    .iter()
+                    .filter(|(e, t)| *e != module && t.instance == tag.instance)
+                    .map(|(e, _)| e);
+                *selected = SelectedModules::from_entities(std::iter::once(module).chain(rest));
+            } else {
+                *selected = SelectedModules::from_entity(module);
+            }
         } else if !buttons.pressed(MouseButton::Left) {
             **interactive_selected = None;
         }
     } else {
-        // if clicky click, unselect stuff
-        if buttons.just_pressed(MouseButton::Left) {
+        // if clicky click, unselect stuff (unless were adding to the selection with a box)
+        if buttons.just_pressed(MouseButton::Left) && !shift {
             selected.clear_selected();
         } else if !buttons.pressed(MouseButton::Left) && let Some(_) = **interactive_selected {
             **interactive_selected = None;
@@ -87,11 +124,14 @@ pub fn place_selected(
     mut q_transform: Query<&mut Transform>,
     q_global_transform: Query<&GlobalTransform>,
     q_children: Query<&Children>,
-    has_io: Query<Or<(With<marker::Input>, With<marker::Output>)>>,
+    // the body turns along with the ports so the ports stay on its edge
+    has_rotatable: Query<Or<(With<marker::Input>, With<marker::Output>, With<BodyType>)>>,
     grid_info: Res<grid::GridInfo>,
     mut q_visibility: Query<&mut Visibility>, 
     has_rigidbody: Query<With<RigidBody>>,
     mut requested_move: EventWriter<RequestedMove>,
+    mut record: EventWriter<RecordEdit>,
+    has_piece: Query<With<PieceKind>>,
 ) {
     let snapping = if keyboard.pressed(KeyCode::LShift) {
         8.0
@@ -103,10 +143,10 @@ pub fn place_selected(
     if mouse_buttons.just_pressed(MouseButton::Left)
         && f32::max(mouse_pos.x.abs(), mouse_pos.y.abs()) < grid_info.half_size
     {
-        // check if any of the colliders are colliding with a rigidbody, ignoring the colliders of the module itself
-        let s_entity = selected.selected.unwrap();
-        let colliders = q_children
-            .iter_descendants(s_entity)
+        // check if any of the colliders are colliding with a rigidbody, ignoring the colliders of the module(s) itself
+        let colliders = selected
+            .all()
+            .flat_map(|e| q_children.iter_descendants(e))
             .filter_map(|e| q_collider.get(e).ok())
             .collect::<Vec<_>>();
         let ignore = colliders.iter().map(|(e, _)| *e).collect::<Vec<_>>();
@@ -127,6 +167,8 @@ pub fn place_selected(
         }) {
             // dont be confused, set selected.place to false so that it now the place_selected fn no longer runs
             selected.place = false;
+            let spawned = selected.all().map(|entity| Edit::Spawn { entity }).collect();
+            record.send(RecordEdit(Edit::Group(spawned)));
             return;
         }
     }
@@ -136,42 +178,172 @@ pub fn place_selected(
     };
     
     // set it to visibile cuz reasons
-    *q_visibility.get_mut(sel_entity).expect("sel_entity is a sprite") = Visibility::VISIBLE;
+    for e in selected.all() {
+        *q_visibility.get_mut(e).expect("sel_entity is a sprite") = Visibility::VISIBLE;
+    }
 
     // if escape is pressed, then clear and return
     if keyboard.pressed(KeyCode::Escape) {
-        commands.entity(sel_entity).despawn_recursive();
+        for e in selected.all() {
+            commands.entity(e).despawn_recursive();
+        }
         selected.clear_selected();
         selected.place = false;
         return;
     }
 
-    let io = q_children
-        .entity(sel_entity)
-        .iter()
-        .filter(|e| has_io.has(**e));
-    if keyboard.just_pressed(KeyCode::Q) {
-        for &e in io {
-            let mut tf = q_transform.entity_mut(e);
-            tf.rotate_z(TAU / 8.0);
-        }
+    // turning a whole group while placing it isnt a thing (yet), place it and then turn it
+    let angle = if keyboard.just_pressed(KeyCode::Q) {
+        TAU / 8.0
     } else if keyboard.just_pressed(KeyCode::E) {
-        for &e in io {
-            let mut tf = q_transform.entity_mut(e);
-            tf.rotate_z(-TAU / 8.0);
+        -TAU / 8.0
+    } else {
+        0.0
+    };
+    if angle != 0.0 && selected.group.is_empty() {
+        if has_piece.has(sel_entity) {
+            // pieces dont have anything on them so they just turn as a whole
+            q_transform.entity_mut(sel_entity).rotate_z(angle);
+        } else {
+            let io = q_children
+                .entity(sel_entity)
+                .iter()
+                .filter(|e| has_rotatable.has(**e));
+            for &e in io {
+                let mut tf = q_transform.entity_mut(e);
+                tf.rotate_z(angle);
+            }
+        }
+    }
+
+    let request = if has_piece.has(sel_entity) {
+        // pieces sit on the grid
+        let grid = grid_info.grid_size;
+        let round = (**mouse_pos / grid).round() * grid;
+        RequestedMove::new(sel_entity, MoveType::TranslateTo(round.extend(0.0))).grid(grid)
+    } else {
+        let Vec2 { x, y } = **mouse_pos - 0.5;
+
+        // rounding x and y to the nearest snapping #
+        let round = Vec2::new(
+            (x / snapping).round() * snapping + 0.5,
+            (y / snapping).round() * snapping + 0.5,
+        );
+        RequestedMove::new(sel_entity, MoveType::TranslateTo(round.extend(0.0))).snapping()
+    };
+    requested_move.send(request.with_followers(selected.group.clone()))
+}
+
+/// drag out a box on empty space to select every module inside it, hold shift to add to the
+/// selection instead of replacing it
+#[allow(clippy::too_many_arguments)]
+pub fn box_select(
+    mut commands: Commands,
+    mut selected: ResMut<SelectedModules>,
+    buttons: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse_pos: Res<CursorCoords>,
+    hovered: Res<HoveredEntities>,
+    has_clickable: Query<Or<(With<BodyType>, With<interact::Interactive>)>>,
+    q_parent: Query<&Parent>,
+    has_piece: Query<With<PieceKind>>,
+    q_modules: Query<
+        (Entity, &Transform),
+        (Or<(With<marker::Module>, With<PieceKind>)>, Without<marker::Locked>)
+    >,
+    mut start: Local<Option<Vec2>>,
+    mut outline: Local<Option<Entity>>,
+) {
+    if let Some(e) = outline.take() {
+        commands.entity(e).despawn();
+    }
+
+    let clickable = |e: &Entity| {
+        has_clickable.has(*e) || q_parent.get(*e).map_or(false, |p| has_piece.has(p.get()))
+    };
+    if buttons.just_pressed(MouseButton::Left) && !hovered.iter().any(clickable) {
+        *start = Some(**mouse_pos);
+    }
+    let Some(from) = *start else {
+        return;
+    };
+    let to = **mouse_pos;
+    let (min, max) = (from.min(to), from.max(to));
+
+    if buttons.pressed(MouseButton::Left) {
+        let mut stroke = StrokeMode::new(Color::rgba(1.0, 1.0, 1.0, 0.6), 0.5);
+        stroke.options.tolerance = 0.01;
+        let rect = shapes::Rectangle {
+            extents: max - min,
+            origin: RectangleOrigin::BottomLeft,
+        };
+        let transform = Transform::from_translation(min.extend(ZOrder::HoverIndicator.f32()));
+        *outline = Some(
+            commands
+                .spawn(GeometryBuilder::build_as(&rect, DrawMode::Stroke(stroke), transform))
+                .name("box_select.sprite")
+                .id()
+        );
+        return;
+    }
+
+    // let go, select everything inside
+    *start = None;
+    // too small, that was just a click
+    if (max - min).max_element() < 2.0 {
+        return;
+    }
+    let inside = q_modules
+        .iter()
+        .filter(|(_, tf)| {
+            let pos = tf.translation.truncate();
+            pos.cmpge(min).all() && pos.cmple(max).all()
+        })
+        .map(|(e, _)| e);
+
+    let shift = keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if shift {
+        for entity in inside {
+            if !selected.contains(entity) {
+                selected.toggle(entity);
+            }
         }
+    } else {
+        *selected = SelectedModules::from_entities(inside);
     }
+}
 
-    let Vec2 { x, y } = **mouse_pos - 0.5;
+/// delete / backspace gets rid of everything selected, the only way to delete pieces since they
+/// dont get widgets
+pub fn delete_selected(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    mut egui_context: ResMut<bevy_egui::EguiContext>,
+    mut selected: ResMut<SelectedModules>,
+    has_locked: Query<With<marker::Locked>>,
+    mut record: EventWriter<RecordEdit>,
+) {
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if !keyboard.any_just_pressed([KeyCode::Delete, KeyCode::Back]) {
+        return;
+    }
 
-    // rounding x and y to the nearest snapping #
-    let round = Vec2::new(
-        (x / snapping).round() * snapping + 0.5,
-        (y / snapping).round() * snapping + 0.5,
+    let doomed = selected
+        .all()
+        .filter(|e| !has_locked.has(*e))
+        .collect::<Vec<_>>();
+    if doomed.is_empty() {
+        return;
+    }
+    record.send(
+        RecordEdit(Edit::Group(doomed.iter().map(|&entity| Edit::Delete { entity }).collect()))
     );
-    requested_move.send(
-        RequestedMove::new(selected.selected.unwrap(), MoveType::TranslateTo(round.extend(0.0))).snapping(),
-    )
+    for e in doomed {
+        commands.entity(e).despawn_recursive();
+    }
+    selected.clear_selected();
 }
 
 #[derive(Resource, Debug, Deref)]
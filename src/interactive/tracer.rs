@@ -1,11 +1,27 @@
+use bevy::ecs::system::SystemParam;
+
+use bevy_egui::{ EguiContext, egui };
+
 use crate::{
-    query::{QueryQueryIter, QueryQuerySimple},
     *, graphics::grid::GridInfo,
+    engine::{ spawn::EditorId, piece::PieceKind },
+    interactive::select::CursorCoords,
+    modules::ModuleComponent,
 };
 use atlas::{basic, AtlasDictionary};
-const TRACER_N: usize = 20;
-#[derive(Deref, DerefMut, Resource)]
-pub struct TracerEntities([Entity; TRACER_N]);
+
+/// how many ticks a trace gets before we give up on it
+const MAX_TICKS: usize = 60 * 5;
+/// put a tick down every this many ticks
+const PER_STEP: usize = 4;
+/// how close the cursor has to be to an input target to say which input it is
+const LABEL_RADIUS: f32 = 6.0;
+/// after this many bounces its probably just rolling around somewhere
+const MAX_BOUNCES: usize = 16;
+/// slower than this after a bounce and its basically stopped
+const MIN_SPEED: f32 = 8.0;
+/// rapier averages the restitution of both colliders and this is the marbles
+const MARBLE_RESTITUTION: f32 = 0.9;
 
 const TRACER_COLOR: Color = Color::Rgba {
     red: 0.25,
@@ -14,43 +30,53 @@ const TRACER_COLOR: Color = Color::Rgba {
     alpha: 0.5,
 };
 
-impl FromWorld for TracerEntities {
-    /// spawn all the tracer entities, invisibly
-    fn from_world(world: &mut World) -> Self {
-        let mut e: TracerEntities = unsafe { std::mem::zeroed() };
+const INPUT_COLOR: Color = Color::Rgba {
+    red: 0.4,
+    green: 0.85,
+    blue: 0.45,
+    alpha: 0.9,
+};
 
-        for i in 0..TRACER_N - 1 {
-            let (texture_atlas, index) = basic::tracer_tick.info();
-            e[i] = world
-                .spawn((SpriteSheetBundle {
-                    texture_atlas,
-                    sprite: TextureAtlasSprite {
-                        index,
-                        color: TRACER_COLOR,
-                        ..default()
-                    },
-                    visibility: Visibility::INVISIBLE,
-                    ..default()
-                },))
-                .name("tracer.sprite")
-                .id();
+/// the color of the trace coming out of output #n, the first one is the same old gray
+pub fn output_color(n: usize) -> Color {
+    if n == 0 {
+        return TRACER_COLOR;
+    }
+    Color::hsla(n as f32 * 137.5 % 360.0, 0.5, 0.5, 0.6)
+}
+
+/// the different sprites tracers can be
+#[derive(Clone, Copy)]
+pub enum TracerSprite {
+    /// the dots along the path
+    Tick,
+    /// the marker at the end (or where it goes into an input)
+    Target,
+}
+
+impl TracerSprite {
+    fn info(self) -> (Handle<TextureAtlas>, usize) {
+        match self {
+            TracerSprite::Tick => basic::tracer_tick.info(),
+            TracerSprite::Target => basic::target.info(),
         }
-        let (texture_atlas, index) = basic::target.info();
-        e[TRACER_N - 1] = world
-            .spawn((SpriteSheetBundle {
-                texture_atlas,
-                sprite: TextureAtlasSprite {
-                    index,
-                    color: TRACER_COLOR,
-                    ..default()
-                },
-                visibility: Visibility::INVISIBLE,
-                ..default()
-            },))
-            .name("tracer.sprite")
-            .id();
+    }
+}
 
-        world
+/// a pool of tracer sprites that grows whenever we need more of them, call `begin` before putting
+/// any down and `finish` after to hide the ones that didnt get used
+#[derive(Resource)]
+pub struct TracerEntities {
+    parent: Entity,
+    ticks: Vec<Entity>,
+    targets: Vec<Entity>,
+    used: (usize, usize),
+}
+
+impl FromWorld for TracerEntities {
+    /// spawn the parent all the tracer entities go under, they get spawned as theyre needed
+    fn from_world(world: &mut World) -> Self {
+        let parent = world
             .spawn((
                 TransformBundle::from_transform(Transform::from_xyz(
                     0.0,
@@ -60,130 +86,360 @@ impl FromWorld for TracerEntities {
                 VisibilityBundle::default(),
             ))
             .name("tracer.parent")
-            .push_children(&*e);
-        e
+            .id();
+        Self {
+            parent,
+            ticks: vec![],
+            targets: vec![],
+            used: (0, 0),
+        }
     }
 }
 
-/// identifies an entity as being a tracer object
-#[derive(Component)]
-pub struct Tracer;
-
-#[allow(clippy::too_many_arguments)]
-pub fn tracer(
-    // mut commands: Commands,
-    rapier_config: Res<RapierConfiguration>,
-    rapier_ctx: Res<RapierContext>,
-    selected: Res<SelectedModules>,
-    q_children: Query<&Children>,
-    mut q_transform: ParamSet<(Query<&mut Transform>, Query<Changed<Transform>>)>,
-    w_out: Query<Entity, With<marker::Output>>,
-    w_sprite: Query<Entity, With<TextureAtlasSprite>>,
-    mut q_visibility: Query<&mut Visibility>,
-    // q_name: Query<&Name>,
-    tracers: Res<TracerEntities>,
-    grid_info: Res<GridInfo>,
-) {
-    // get the timestep factor
-    let factor = match rapier_config.timestep_mode {
-        TimestepMode::Fixed { dt, .. } => dt,
-        TimestepMode::Variable { max_dt, .. } => max_dt,
-        TimestepMode::Interpolated { dt, .. } => dt,
-    };
+impl TracerEntities {
+    pub fn begin(&mut self) {
+        self.used = (0, 0);
+    }
 
-    let per_step = 4;
+    /// put down a sprite at `pos`, reusing one if theres one free
+    pub fn show(&mut self, commands: &mut Commands, kind: TracerSprite, pos: Vec2, color: Color) {
+        let (pool, used) = match kind {
+            TracerSprite::Tick => (&mut self.ticks, &mut self.used.0),
+            TracerSprite::Target => (&mut self.targets, &mut self.used.1),
+        };
+        let (texture_atlas, index) = kind.info();
+        let sprite = TextureAtlasSprite {
+            index,
+            color,
+            ..default()
+        };
+        let transform = Transform::from_translation(pos.extend(2.0));
 
-    let selected = selected.selected.unwrap();
-    let p_pos = q_transform.p0().entity(selected).translation.truncate();
+        if let Some(&e) = pool.get(*used) {
+            commands
+                .entity(e)
+                .insert((sprite, transform, Visibility::VISIBLE));
+        } else {
+            let e = commands
+                .spawn(SpriteSheetBundle {
+                    texture_atlas,
+                    sprite,
+                    transform,
+                    ..default()
+                })
+                .name("tracer.sprite")
+                .id();
+            commands.entity(self.parent).add_child(e);
+            pool.push(e);
+        }
+        *used += 1;
+    }
 
-    for tracer in **tracers {
-        *q_visibility.entity_mut(tracer) = Visibility::INVISIBLE;
+    /// hide everything that didnt get used since `begin`
+    pub fn finish(&self, q_visibility: &mut Query<&mut Visibility>) {
+        let unused = self.ticks[self.used.0..]
+            .iter()
+            .chain(self.targets[self.used.1..].iter());
+        for &e in unused {
+            if let Ok(mut visibility) = q_visibility.get_mut(e) {
+                *visibility = Visibility::INVISIBLE;
+            }
+        }
     }
 
-    for entity in q_children.entity(selected).iter().with(&w_out) {
-        // gotta love vector math
-        if q_transform.p1().get(entity).is_err() {
-            return;
+    /// draw a trajectory
+    pub fn show_trajectory(&mut self, commands: &mut Commands, trajectory: &Trajectory, color: Color) {
+        for &point in trajectory.points.iter() {
+            self.show(commands, TracerSprite::Tick, point, color);
+        }
+        // bounces get the solid version of the color so theyre easier to spot
+        for &point in trajectory.bounces.iter() {
+            self.show(commands, TracerSprite::Tick, point, color.with_a(1.0));
         }
-        let mut q_transform = q_transform.p0();
-        // get the transform of the actual output graphic thing itself
-        let mut transform = *q_transform.entity(
-            q_children
-                .entity(entity)
-                .iter()
-                .with(&w_sprite)
-                .next()
-                .unwrap(),
-        );
+        match trajectory.end {
+            TraceEnd::Input { at, .. } => self.show(commands, TracerSprite::Target, at, INPUT_COLOR),
+            TraceEnd::Stopped(at) => self.show(commands, TracerSprite::Target, at, color),
+            TraceEnd::OutOfBounds => {}
+        }
+    }
+}
+
+/// how a trace ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceEnd {
+    /// went into an input, `index` is which of its modules inputs it is
+    Input {
+        at: Vec2,
+        input: Entity,
+        index: usize,
+    },
+    /// came to a stop here (or we gave up on it)
+    Stopped(Vec2),
+    /// fell off the grid
+    OutOfBounds,
+}
+
+/// the path a marble would take
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    /// where its at every `PER_STEP` ticks
+    pub points: Vec<Vec2>,
+    /// everywhere it bounced off of something
+    pub bounces: Vec<Vec2>,
+    pub end: TraceEnd,
+}
+
+/// everything needed to figure out where a marble is gonna go
+#[derive(SystemParam)]
+pub struct Tracing<'w, 's> {
+    rapier_config: Res<'w, RapierConfiguration>,
+    rapier_ctx: Res<'w, RapierContext>,
+    grid_info: Res<'w, GridInfo>,
+    q_children: Query<'w, 's, &'static Children>,
+    q_transform: Query<'w, 's, &'static Transform>,
+    q_global_transform: Query<'w, 's, &'static GlobalTransform>,
+    q_restitution: Query<'w, 's, &'static Restitution>,
+    w_muzzle: Query<'w, 's, Entity, With<marker::Muzzle>>,
+    q_input: Query<'w, 's, &'static marker::Input>,
+}
+
+impl<'w, 's> Tracing<'w, 's> {
+    /// where a marble fired out of this output starts off and how fast its going
+    pub fn launch(&self, module: Entity, output: Entity) -> Option<(Vec2, Vec2)> {
+        let q_transform = &self.q_transform;
+        let p_pos = q_transform.get(module).ok()?.translation.truncate();
+        // get the transform of where the marble actually comes out
+        let muzzle = self.q_children
+            .get(output)
+            .ok()?
+            .iter()
+            .copied()
+            .find(|e| self.w_muzzle.contains(*e))?;
+        let mut transform = *q_transform.get(muzzle).ok()?;
         let z = transform.translation.z;
-        let rot = q_transform.entity(entity).rotation;
+        let rot = q_transform.get(output).ok()?.rotation;
         transform.rotate_around(Vec3::Z * z, rot);
 
         // borrowed from marble_io::spawn_marbles, if you change that and this breaks thats why
-        let mut shape_pos = transform.translation.truncate() + p_pos;
-        let shape_rot = transform.rotation;
-        let mut shape_vel = shape_rot.mul_vec3(Vec3::X).truncate() * marble_io::VELOCITY_FACTOR;
+        let pos = transform.translation.truncate() + p_pos;
+        let vel = transform.rotation.mul_vec3(Vec3::X).truncate() * marble_io::VELOCITY_FACTOR;
+        Some((pos, vel))
+    }
+
+    /// follow a marble through the world, bouncing off of whatever it hits until it goes into an
+    /// input, falls off the grid or gives up
+    pub fn trace(&self, mut pos: Vec2, mut vel: Vec2) -> Trajectory {
+        // one sim tick, fast forwarding just runs more of them
+        let dt = sim::TICK;
+        let gravity = self.rapier_config.gravity;
+        let ball = Collider::ball(marble::MARBLE_RADIUS);
+
+        let mut points = vec![];
+        let mut bounces = vec![];
 
         // update it such that its out of the output module
-        for _ in 0..per_step {
-            shape_vel += rapier_config.gravity * factor;
-            shape_pos += shape_vel * factor;
+        for _ in 0..PER_STEP {
+            vel += gravity * dt;
+            pos += vel * dt;
         }
 
-        // step through until either we rapier scene query turn up bad or we do <x> steps
-        'tracer: for &tracer in tracers.iter() {
-            if !grid_info.in_bounds(shape_pos) {
-                return
+        let is_input = |e| self.q_input.contains(e);
+        let inputs = QueryFilter::new().predicate(&is_input);
+        let solid = QueryFilter::only_fixed().exclude_sensors();
+
+        for tick in 0..MAX_TICKS {
+            if !self.grid_info.in_bounds(pos) {
+                return Trajectory { points, bounces, end: TraceEnd::OutOfBounds };
             }
-            
-            // update the tracers
-            *q_visibility.entity_mut(tracer) = Visibility::VISIBLE;
-            let mut shape_transform = q_transform.entity_mut(tracer);
-            *shape_transform = Transform::from_translation(shape_pos.extend(2.0));
-
-            let filter = QueryFilter::only_fixed().exclude_sensors();
-
-            for _ in 0..per_step {
-                shape_vel += rapier_config.gravity * factor;
-                shape_pos += shape_vel * factor;
-                // if the shape collides
-                if let Some((_, _)) = rapier_ctx.cast_shape(
-                    shape_pos,
-                    0.0,
-                    shape_vel * factor,
-                    &Collider::ball(basic::marble_small.width() * 0.5),
-                    1.0,
-                    filter,
-                ) {
-                    let mut current = shape_vel * factor / 2.0;
-                    let goal = 1.0 / 1024.0;
-                    // shape_pos += shape_vel * factor;
-                    // step it closer to the colliding shape
-                    while current.length() > goal {
-                        if rapier_ctx
-                            .intersection_with_shape(
-                                shape_pos,
-                                0.0,
-                                &Collider::ball(basic::marble_small.width() * 0.5),
-                                filter,
-                            )
-                            .is_some()
-                        {
-                            shape_pos -= current;
-                        } else {
-                            shape_pos += current;
-                        }
-                        current /= 2.0;
-                    }
-
-                    let tracer = tracers[TRACER_N - 1];
-                    *q_visibility.entity_mut(tracer) = Visibility::VISIBLE;
-                    let mut shape_transform = q_transform.entity_mut(tracer);
-                    *shape_transform = Transform::from_translation(shape_pos.extend(2.0));
-
-                    break 'tracer;
-                }
+            if tick % PER_STEP == 0 {
+                points.push(pos);
+            }
+
+            vel += gravity * dt;
+            let motion = vel * dt;
+
+            let hit_wall = self.rapier_ctx.cast_shape(pos, 0.0, motion, &ball, 1.0, solid);
+            if let Some((input, toi)) = self.rapier_ctx.cast_shape(pos, 0.0, motion, &ball, 1.0, inputs)
+                && hit_wall.map_or(true, |(_, wall)| toi.toi <= wall.toi)
+            {
+                let at = self.q_global_transform
+                    .get(input)
+                    .map_or(pos + motion * toi.toi, |t| t.translation().truncate());
+                let index = self.q_input.get(input).map_or(0, |n| **n);
+                return Trajectory { points, bounces, end: TraceEnd::Input { at, input, index } };
+            }
+
+            let Some((collider, toi)) = hit_wall else {
+                pos += motion;
+                continue;
+            };
+
+            // step up to where it hits and bounce off
+            pos += motion * toi.toi;
+            let only = |e| e == collider;
+            let normal = self.rapier_ctx
+                .project_point(pos, true, QueryFilter::new().predicate(&only))
+                .map(|(_, proj)| (pos - proj.point).normalize_or_zero())
+                .filter(|n| *n != Vec2::ZERO)
+                .unwrap_or(-motion.normalize_or_zero());
+            let restitution = self.q_restitution.get(collider).map_or(0.0, |r| r.coefficient);
+            let restitution = (restitution + MARBLE_RESTITUTION) / 2.0;
+
+            let into = vel.dot(normal);
+            if into < 0.0 {
+                vel -= (1.0 + restitution) * into * normal;
+            }
+            // nudge it off the surface so it doesnt hit the same thing again right away
+            pos += normal * 0.01;
+            bounces.push(pos);
+
+            if bounces.len() >= MAX_BOUNCES || vel.length() < MIN_SPEED {
+                return Trajectory { points, bounces, end: TraceEnd::Stopped(pos) };
+            }
+        }
+        Trajectory { points, bounces, end: TraceEnd::Stopped(pos) }
+    }
+
+    /// every output of this module with the trajectory coming out of it, in order
+    pub fn trace_module(
+        &self,
+        module: Entity,
+        w_out: &Query<(Entity, &marker::Output)>
+    ) -> Vec<(usize, Trajectory)> {
+        let Ok(children) = self.q_children.get(module) else {
+            return vec![];
+        };
+        let mut outputs = children
+            .iter()
+            .filter_map(|e| w_out.get(*e).ok())
+            .map(|(e, n)| (e, **n))
+            .collect::<Vec<_>>();
+        outputs.sort_by_key(|(_, n)| *n);
+        outputs
+            .into_iter()
+            .filter_map(|(output, n)| {
+                let (pos, vel) = self.launch(module, output)?;
+                Some((n, self.trace(pos, vel)))
+            })
+            .collect()
+    }
+}
+
+/// show where the marbles coming out of every output of the selected module are gonna go, hovering
+/// over where one goes into an input says which input that is
+#[allow(clippy::too_many_arguments)]
+pub fn tracer(
+    mut commands: Commands,
+    mut egui_context: ResMut<EguiContext>,
+    selected: Res<SelectedModules>,
+    mouse_pos: Res<CursorCoords>,
+    tracing: Tracing,
+    w_out: Query<(Entity, &marker::Output)>,
+    q_parent: Query<&Parent>,
+    q_module: Query<&ModuleComponent>,
+    mut q_visibility: Query<&mut Visibility>,
+    mut tracers: ResMut<TracerEntities>,
+) {
+    tracers.begin();
+    let mut hovered = vec![];
+    if let Some(selected) = selected.selected.filter(|_| !selected.place) {
+        for (n, trajectory) in tracing.trace_module(selected, &w_out) {
+            tracers.show_trajectory(&mut commands, &trajectory, output_color(n));
+            if let TraceEnd::Input { at, input, index } = trajectory.end
+                && at.distance(**mouse_pos) < LABEL_RADIUS
+            {
+                // the sensor sits a couple levels under the module it belongs to
+                let module = q_parent
+                    .iter_ancestors(input)
+                    .find_map(|e| q_module.get(e).ok());
+                hovered.push(match module {
+                    Some(module) => format!("output {n} goes into input {index} of {}", module.ty.get_name()),
+                    None => format!("output {n} goes into input {index}"),
+                });
+            }
+        }
+    }
+    tracers.finish(&mut q_visibility);
+
+    if !hovered.is_empty() {
+        egui::show_tooltip_at_pointer(egui_context.ctx_mut(), egui::Id::new("tracer.input"), |ui| {
+            for line in hovered {
+                ui.label(line);
+            }
+        });
+    }
+}
+
+/// whether to draw where every output in the machine is going, toggled with T
+#[derive(Resource, Default, Debug)]
+pub struct TrajectoryOverlay {
+    pub enabled: bool,
+}
+
+/// the overlay gets its own sprites so it doesnt fight with the selected modules tracers
+#[derive(Resource, Deref, DerefMut)]
+pub struct OverlayTracers(TracerEntities);
+
+impl FromWorld for OverlayTracers {
+    fn from_world(world: &mut World) -> Self {
+        Self(TracerEntities::from_world(world))
+    }
+}
+
+/// the color of everything coming out of this module in the overlay
+fn module_color(id: Option<&EditorId>) -> Color {
+    let n = id.map_or(0, |id| id.0);
+    Color::hsla((n as f32 * 137.5) % 360.0, 0.6, 0.55, 0.5)
+}
+
+pub fn toggle_overlay(
+    keyboard: Res<Input<KeyCode>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut overlay: ResMut<TrajectoryOverlay>,
+) {
+    if keyboard.just_pressed(KeyCode::T) && !egui_context.ctx_mut().wants_keyboard_input() {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+/// draw the trajectories out of every output in the machine, only redone when something moves
+/// and once more the frame after
+#[allow(clippy::too_many_arguments)]
+pub fn trajectory_overlay(
+    mut commands: Commands,
+    overlay: Res<TrajectoryOverlay>,
+    tracing: Tracing,
+    w_out: Query<(Entity, &marker::Output)>,
+    q_modules: Query<(Entity, Option<&EditorId>), With<marker::Module>>,
+    q_changed: Query<
+        (),
+        (
+            Changed<Transform>,
+            Or<(With<marker::Module>, With<PieceKind>, With<marker::Input>, With<marker::Output>)>,
+        )
+    >,
+    removed_modules: RemovedComponents<marker::Module>,
+    removed_pieces: RemovedComponents<PieceKind>,
+    mut q_visibility: Query<&mut Visibility>,
+    mut tracers: ResMut<OverlayTracers>,
+    mut stale: Local<bool>,
+) {
+    let removed = removed_modules.iter().next().is_some() || removed_pieces.iter().next().is_some();
+    let changed = overlay.is_changed() || !q_changed.is_empty() || removed;
+    // rapier only finds out about whatever moved in the SimTick stage after this, so anything that
+    // changed gets traced again next frame once it has
+    if !std::mem::replace(&mut *stale, changed) && !changed {
+        return;
+    }
+
+    tracers.begin();
+    if overlay.enabled {
+        for (module, id) in q_modules.iter() {
+            let color = module_color(id);
+            for (_, trajectory) in tracing.trace_module(module, &w_out) {
+                tracers.show_trajectory(&mut commands, &trajectory, color);
             }
         }
     }
+    tracers.finish(&mut q_visibility);
 }
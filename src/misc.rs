@@ -16,6 +16,22 @@ pub mod marker {
     #[derive(Component, Deref, DerefMut)]
     pub struct Output(pub usize);
 
+    /// marks the spot on an output that marbles get fired from
+    #[derive(Component)]
+    pub struct Muzzle;
+
+    /// marks the module standing in for a level input
+    #[derive(Component, Deref, DerefMut)]
+    pub struct LevelInput(pub usize);
+
+    /// marks the module standing in for a level output
+    #[derive(Component, Deref, DerefMut)]
+    pub struct LevelOutput(pub usize);
+
+    /// marks modules the player isn't allowed to move or delete
+    #[derive(Component)]
+    pub struct Locked;
+
     /// marks those funny indicator lights
     #[derive(Component)]
     pub struct Indicator;
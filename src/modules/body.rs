@@ -2,6 +2,7 @@ use crate::{
     graphics::atlas::{basic, AtlasDictionary},
     *,
 };
+use bevy_rapier2d::rapier::{ math::{ Point, Vector }, parry::query::{ Ray, RayCast } };
 use serde::{Serialize, Deserialize};
 
 #[derive(Default, Copy, Clone, Debug, Serialize, Deserialize, Component)]
@@ -9,6 +10,10 @@ pub enum BodyType {
     #[default]
     Small,
     Large,
+    /// a capsule, wide enough to fit a row of ports along the top and bottom
+    Wide,
+    /// a chamfered square
+    Block,
 }
 
 pub fn offset_of<T: AtlasDictionary>(input: T) -> f32 {
@@ -27,6 +32,20 @@ impl BodyType {
         match self {
             Small => Ball,
             Large => Ball,
+            // 0.5 smaller than the sprite, same as the balls
+            Wide => Other(Collider::capsule_x(8.0, 6.5)),
+            Block => {
+                let (a, b) = (10.5, 5.0);
+                let points = [(a, b), (b, a), (-b, a), (-a, b), (-a, -b), (-b, -a), (b, -a), (a, -b)];
+                Other(
+                    Collider::convex_polyline(
+                        points
+                            .iter()
+                            .map(|&(x, y)| Vec2::new(x, y))
+                            .collect()
+                    ).unwrap()
+                )
+            }
         }
     }
     
@@ -38,6 +57,8 @@ impl BodyType {
         match self {
             Small => cyanish,
             Large => cyanish,
+            Wide => cyanish,
+            Block => cyanish,
         }
     }
     
@@ -46,11 +67,31 @@ impl BodyType {
         match self {
             Small => basic::body_small,
             Large => basic::body,
+            Wide => basic::body_wide,
+            Block => basic::body_block,
         }
     }
     
+    /// how far the edge of the body is from its center along the x axis
     pub fn offset(self) -> f32 {
-        offset_of(self.sprite())
+        self.offset_at(0.0)
+    }
+
+    /// how far the edge of the body is from its center in the direction of `angle` (radians), plus
+    /// one so its the same as `offset_of` for balls
+    pub fn offset_at(self, angle: f32) -> f32 {
+        use ColliderType::*;
+        match self.collider_type() {
+            Ball => offset_of(self.sprite()),
+            Other(collider) => {
+                // cast from way outside back towards the center, where it hits is the edge
+                let far = 1000.0;
+                let dir = Vector::new(angle.cos(), angle.sin());
+                let ray = Ray::new(Point::from(dir * far), -dir);
+                let toi = collider.raw.cast_local_ray(&ray, far, true).unwrap_or(far);
+                far - toi + 1.0
+            }
+        }
     }
 
     pub fn color(self) -> Color {
@@ -64,10 +105,8 @@ impl BodyType {
     pub fn collider(self) -> Collider {
         use ColliderType::*;
         match self.collider_type() {
-            Ball => Collider::ball(self.offset() - 1.0),
+            Ball => Collider::ball(offset_of(self.sprite()) - 1.0),
             Other(collider) => collider,
         }
     }
-    
-    
 }
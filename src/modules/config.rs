@@ -0,0 +1,163 @@
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use bevy_egui::egui::{ self, Ui };
+
+use super::Module;
+
+/// how long modules wait before doing their thing unless theyre set up otherwise, for
+/// `#[serde(default = "default_delay")]`
+pub fn default_delay() -> f32 {
+    0.15
+}
+
+/// how hard modules fire marbles unless theyre set up otherwise
+pub fn default_power() -> f32 {
+    1.0
+}
+
+/// what kind of setting a [`ModuleParam`] is, and what it points to
+pub enum Param<'a> {
+    /// how long the module waits before doing its thing, in seconds
+    Delay(&'a mut f32),
+    /// a plain old number
+    Int(&'a mut i32, RangeInclusive<i32>),
+    /// how hard marbles get fired, `1.0` being normal
+    Power(&'a mut f32),
+    /// which of the module's outputs to use
+    Output(&'a mut usize, usize),
+}
+
+impl Param<'_> {
+    /// what its set to right now
+    pub fn value(&self) -> ParamValue {
+        match self {
+            Param::Delay(delay) => ParamValue::Delay(**delay),
+            Param::Int(value, _) => ParamValue::Int(**value),
+            Param::Power(power) => ParamValue::Power(**power),
+            Param::Output(output, _) => ParamValue::Output(**output),
+        }
+    }
+
+    /// set it to `value`, as long as its the same kind of setting
+    pub fn set(&mut self, value: ParamValue) {
+        match (self, value) {
+            (Param::Delay(delay), ParamValue::Delay(value)) => **delay = value,
+            (Param::Int(int, _), ParamValue::Int(value)) => **int = value,
+            (Param::Power(power), ParamValue::Power(value)) => **power = value,
+            (Param::Output(output, _), ParamValue::Output(value)) => **output = value,
+            (param, value) => warn!("cant set a {} to {value:?}", param.kind()),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Param::Delay(_) => "delay",
+            Param::Int(..) => "number",
+            Param::Power(_) => "power",
+            Param::Output(..) => "output",
+        }
+    }
+}
+
+/// what a [`Param`] is set to, without the module its a part of. undoing a config change puts
+/// these back instead of the whole module so whatever its got stored stays where it is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamValue {
+    Delay(f32),
+    Int(i32),
+    Power(f32),
+    Output(usize),
+}
+
+/// what every setting of a module is set to right now
+pub fn param_values(module: &mut dyn Module) -> Vec<ParamValue> {
+    module.params().iter().map(|p| p.param.value()).collect()
+}
+
+/// put back what [`param_values`] took, leaving the rest of the module alone
+pub fn set_param_values(module: &mut dyn Module, values: &[ParamValue]) {
+    for (param, value) in module.params().iter_mut().zip(values) {
+        param.param.set(*value);
+        param.validate();
+    }
+}
+
+/// a setting a module exposes so it can be changed from the config panel
+pub struct ModuleParam<'a> {
+    pub name: &'static str,
+    pub param: Param<'a>,
+}
+
+impl<'a> ModuleParam<'a> {
+    pub fn new(name: &'static str, param: Param<'a>) -> Self {
+        Self { name, param }
+    }
+
+    /// clamp the value into whatever range makes sense for it
+    pub fn validate(&mut self) {
+        use Param::*;
+        match &mut self.param {
+            Delay(delay) => **delay = delay.clamp(0.0, 10.0),
+            Int(value, range) => **value = (**value).clamp(*range.start(), *range.end()),
+            Power(power) => **power = power.clamp(0.1, 4.0),
+            Output(output, outputs) => **output = (**output).min(outputs.saturating_sub(1)),
+        }
+    }
+
+    /// draw the widget for this setting, returns whether it was changed
+    fn ui(&mut self, ui: &mut Ui) -> bool {
+        use Param::*;
+        ui.label(self.name);
+        let changed = match &mut self.param {
+            Delay(delay) => {
+                ui.add(
+                    egui::DragValue::new(*delay).speed(0.01).clamp_range(0.0..=10.0).suffix("s")
+                ).changed()
+            }
+            Int(value, range) => {
+                ui.add(egui::DragValue::new(*value).clamp_range(range.clone())).changed()
+            }
+            Power(power) => {
+                ui.add(egui::Slider::new(*power, 0.1..=4.0).suffix("x")).changed()
+            }
+            Output(output, outputs) => {
+                let mut changed = false;
+                ui.horizontal(|ui| {
+                    for i in 0..*outputs {
+                        changed |= ui.selectable_value(*output, i, format!("#{}", i + 1)).changed();
+                    }
+                });
+                changed
+            }
+        };
+        ui.end_row();
+        changed
+    }
+}
+
+/// sent whenever a module's config gets changed from the config panel, so it can be undone
+pub struct ModuleConfigChanged {
+    pub entity: Entity,
+    pub before: Vec<ParamValue>,
+    pub after: Vec<ParamValue>,
+}
+
+/// draw the config panel for this module, returns whether anything was changed
+pub fn config_ui(ui: &mut Ui, module: &mut dyn Module) -> bool {
+    let mut params = module.params();
+    if params.is_empty() {
+        return false;
+    }
+
+    let mut changed = false;
+    egui::Grid::new("module config")
+        .striped(true)
+        .show(ui, |ui| {
+            for param in params.iter_mut() {
+                changed |= param.ui(ui);
+                param.validate();
+            }
+        });
+    changed
+}
@@ -0,0 +1,209 @@
+use super::*;
+
+/// define a module that waits for a marble in both of its inputs, then fires a number marble with
+/// the result of `$op`
+macro_rules! binary_op {
+    ($name:ident, $display:literal, $identifier:literal, |$a:ident, $b:ident| $op:expr) => {
+        #[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+        pub struct $name {
+            #[serde(default = "default_delay")]
+            delay: f32,
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name { delay: default_delay() }
+            }
+        }
+
+        #[typetag::serde]
+        impl Module for $name {
+            fn info(&self) -> ModuleInfo {
+                ModuleInfo {
+                    instructions: SpawnInstructions::from_body(BodyType::Wide)
+                        .with_input_rotations([-150.0, 150.0].into_iter(), 0.0)
+                        .with_output_rotations([0.0].into_iter(), 0.0),
+                    name: $display.into(),
+                    identifier: $identifier.into(),
+                }
+            }
+
+            fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+                events.send(UpdateIndicatorColors);
+
+                if state.input_state.iter().all(Option::is_some) {
+                    events.send(Callback(self.delay));
+                }
+            }
+
+            fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+                if let [Some($a), Some($b)] = state.input_state[..] {
+                    let ($a, $b) = ($a.get_val(), $b.get_val());
+                    events.send(FireMarble(Marble::num($op)));
+                    state.input_state.fill(None);
+                    events.send(UpdateIndicatorColors);
+                }
+            }
+
+            fn params(&mut self) -> Vec<ModuleParam<'_>> {
+                vec![ModuleParam::new("Delay", Param::Delay(&mut self.delay))]
+            }
+        }
+    };
+}
+
+binary_op!(Adder, "Adder", "add.arithmetic", |a, b| a.wrapping_add(b));
+binary_op!(Subtractor, "Subtractor", "sub.arithmetic", |a, b| a.wrapping_sub(b));
+
+/// compares its two inputs and fires a bit out of the output for less than, equal to, or greater
+/// than (top to bottom)
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Comparator {
+    #[serde(default = "default_delay")]
+    delay: f32,
+}
+
+impl Default for Comparator {
+    fn default() -> Self {
+        Comparator { delay: default_delay() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Comparator {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Block)
+                .with_input_rotations([-150.0, 150.0].into_iter(), 0.0)
+                .with_output_rotations([45.0, 0.0, -45.0].into_iter(), 0.0),
+            name: "Comparator".into(),
+            identifier: "compare.arithmetic".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state.iter().all(Option::is_some) {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if let [Some(a), Some(b)] = state.input_state[..] {
+            use std::cmp::Ordering::*;
+            let output = match a.get_val().cmp(&b.get_val()) {
+                Less => 0,
+                Equal => 1,
+                Greater => 2,
+            };
+            events.send(FireMarbleFrom(Marble::bit(true), output, 1.0));
+            state.input_state.fill(None);
+            events.send(UpdateIndicatorColors);
+        }
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![ModuleParam::new("Delay", Param::Delay(&mut self.delay))]
+    }
+}
+
+/// adds one to whatever goes into it
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Incrementer {
+    #[serde(default = "default_delay")]
+    delay: f32,
+}
+
+impl Default for Incrementer {
+    fn default() -> Self {
+        Incrementer { delay: default_delay() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Incrementer {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_input_rotations([-180.0].into_iter(), 0.0)
+                .with_output_rotations([0.0].into_iter(), 0.0),
+            name: "Incrementer".into(),
+            identifier: "inc.arithmetic".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state[0].is_some() {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if let Some(marble) = state.input_state[0].take() {
+            events.send(FireMarble(Marble::num(marble.get_val().wrapping_add(1))));
+            events.send(UpdateIndicatorColors);
+        }
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![ModuleParam::new("Delay", Param::Delay(&mut self.delay))]
+    }
+}
+
+/// fires a number marble with its value whenever anything goes into it
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Constant {
+    value: i32,
+    #[serde(default = "default_delay")]
+    delay: f32,
+}
+
+impl Default for Constant {
+    fn default() -> Self {
+        Constant { value: 0, delay: default_delay() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Constant {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_input_rotations([-180.0].into_iter(), 0.0)
+                .with_output_rotations([0.0].into_iter(), 0.0),
+            name: "Constant".into(),
+            identifier: "const.arithmetic".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state[0].is_some() {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if state.input_state[0].take().is_some() {
+            events.send(FireMarble(Marble::num(self.value)));
+            events.send(UpdateIndicatorColors);
+        }
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![
+            ModuleParam::new("Value", Param::Int(&mut self.value, i32::MIN..=i32::MAX)),
+            ModuleParam::new("Delay", Param::Delay(&mut self.delay))
+        ]
+    }
+
+    fn debug_ui(&mut self, ui: &mut Ui, events: &mut ModuleEventSender, _: &ModuleState) {
+        if ui.button("Fire Marble!").clicked() {
+            events.send(FireMarble(Marble::num(self.value)));
+        }
+    }
+}
@@ -1,11 +1,16 @@
 use super::*;
 
 #[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
-pub struct Basic;
+pub struct Basic {
+    #[serde(default = "default_delay")]
+    delay: f32,
+    #[serde(default = "default_power")]
+    power: f32,
+}
 
 impl Default for Basic {
     fn default() -> Self {
-        Basic
+        Basic { delay: default_delay(), power: default_power() }
     }
 }
 
@@ -16,8 +21,8 @@ impl Module for Basic {
             instructions: SpawnInstructions::from_body(BodyType::Small)
                 .with_input_rotations([-180.0].into_iter(), 0.0)
                 .with_output_rotations([0.0].into_iter(), 0.0),
-            name: "Basic Module",
-            identifier: "basic.module",
+            name: "Basic Module".into(),
+            identifier: "basic.module".into(),
         }
     }
 
@@ -25,7 +30,7 @@ impl Module for Basic {
         events.send(UpdateIndicatorColors);
 
         if state.input_state[0].is_some() {
-            events.send(Callback(0.15));
+            events.send(Callback(self.delay));
         }
     }
 
@@ -33,9 +38,66 @@ impl Module for Basic {
         let input_state = &mut state.input_state;
 
         if let Some(marble) = input_state[0] {
-            events.send(FireMarble(marble));
+            events.send(FireMarbleFrom(marble, 0, self.power));
             input_state[0] = None;
             events.send(UpdateIndicatorColors);
         }
     }
-}
\ No newline at end of file
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![
+            ModuleParam::new("Delay", Param::Delay(&mut self.delay)),
+            ModuleParam::new("Power", Param::Power(&mut self.power))
+        ]
+    }
+}
+
+/// passes marbles through like a basic module, but you get to pick which output they go out of
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Switch {
+    #[serde(default = "default_delay")]
+    delay: f32,
+    #[serde(default)]
+    output: usize,
+}
+
+impl Default for Switch {
+    fn default() -> Self {
+        Switch { delay: default_delay(), output: 0 }
+    }
+}
+
+#[typetag::serde]
+impl Module for Switch {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_input_rotations([-180.0].into_iter(), 0.0)
+                .with_output_rotations([30.0, -30.0].into_iter(), 0.0),
+            name: "Switch".into(),
+            identifier: "switch.module".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state[0].is_some() {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if let Some(marble) = state.input_state[0].take() {
+            events.send(FireMarbleFrom(marble, self.output, 1.0));
+            events.send(UpdateIndicatorColors);
+        }
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![
+            ModuleParam::new("Delay", Param::Delay(&mut self.delay)),
+            ModuleParam::new("Output", Param::Output(&mut self.output, 2))
+        ]
+    }
+}
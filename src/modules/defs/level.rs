@@ -0,0 +1,51 @@
+use super::*;
+
+/// where the level's marbles come from, fired by the level rather than by any inputs
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug, Default)]
+pub struct LevelInput;
+
+#[typetag::serde]
+impl Module for LevelInput {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_output_rotations([0.0].into_iter(), 0.0),
+            name: "Level Input".into(),
+            identifier: "input.level".into(),
+        }
+    }
+
+    fn update(&mut self, _: &mut ModuleEventSender, _: &mut ModuleState) {}
+
+    fn callback_update(&mut self, _: &mut ModuleEventSender, _: &mut ModuleState) {}
+
+    fn debug_ui(&mut self, ui: &mut Ui, _: &mut ModuleEventSender, _: &ModuleState) {
+        ui.label("Marbles come out of here when a test case is run.");
+    }
+}
+
+/// where the level's marbles need to end up, whatever lands in here gets recorded for grading
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug, Default)]
+pub struct LevelOutput;
+
+#[typetag::serde]
+impl Module for LevelOutput {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_input_rotations([-180.0].into_iter(), 0.0),
+            name: "Level Output".into(),
+            identifier: "output.level".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, _: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+    }
+
+    fn callback_update(&mut self, _: &mut ModuleEventSender, _: &mut ModuleState) {}
+
+    fn debug_ui(&mut self, ui: &mut Ui, _: &mut ModuleEventSender, _: &ModuleState) {
+        ui.label("Marbles that land in here get checked against the level.");
+    }
+}
@@ -0,0 +1,109 @@
+use super::*;
+
+/// define a gate that waits for a marble in both of its inputs, then fires the result of `$op`
+macro_rules! binary_gate {
+    ($name:ident, $display:literal, $identifier:literal, |$a:ident, $b:ident| $op:expr) => {
+        #[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+        pub struct $name {
+            #[serde(default = "default_delay")]
+            delay: f32,
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name { delay: default_delay() }
+            }
+        }
+
+        #[typetag::serde]
+        impl Module for $name {
+            fn info(&self) -> ModuleInfo {
+                ModuleInfo {
+                    instructions: SpawnInstructions::from_body(BodyType::Large)
+                        .with_input_rotations([-150.0, 150.0].into_iter(), 0.0)
+                        .with_output_rotations([0.0].into_iter(), 0.0),
+                    name: $display.into(),
+                    identifier: $identifier.into(),
+                }
+            }
+
+            fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+                events.send(UpdateIndicatorColors);
+
+                if state.input_state.iter().all(Option::is_some) {
+                    events.send(Callback(self.delay));
+                }
+            }
+
+            fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+                if let [Some($a), Some($b)] = state.input_state[..] {
+                    let ($a, $b) = ($a.get_bit(), $b.get_bit());
+                    events.send(FireMarble(Marble::bit($op)));
+                    state.input_state.fill(None);
+                    events.send(UpdateIndicatorColors);
+                }
+            }
+
+            fn params(&mut self) -> Vec<ModuleParam<'_>> {
+                vec![ModuleParam::new("Delay", Param::Delay(&mut self.delay))]
+            }
+        }
+    };
+}
+
+binary_gate!(And, "AND Gate", "and.logic", |a, b| a && b);
+binary_gate!(Or, "OR Gate", "or.logic", |a, b| a || b);
+binary_gate!(Xor, "XOR Gate", "xor.logic", |a, b| a ^ b);
+
+/// flips whatever bit goes into it
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Not {
+    #[serde(default = "default_delay")]
+    delay: f32,
+}
+
+impl Default for Not {
+    fn default() -> Self {
+        Not { delay: default_delay() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Not {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_input_rotations([-180.0].into_iter(), 0.0)
+                .with_output_rotations([0.0].into_iter(), 0.0),
+            name: "NOT Gate".into(),
+            identifier: "not.logic".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state[0].is_some() {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if let Some(marble) = state.input_state[0].take() {
+            events.send(FireMarble(Marble::bit(!marble.get_bit())));
+            events.send(UpdateIndicatorColors);
+        }
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![ModuleParam::new("Delay", Param::Delay(&mut self.delay))]
+    }
+}
+
+#[test]
+fn test_old_gates_load() {
+    // gates didnt have anything in them before they got a delay
+    let mut gate: Box<dyn Module> = ron::de::from_str(r#"{"type":"And"}"#).unwrap();
+    let params = gate.params();
+    assert!(matches!(&params[0].param, Param::Delay(delay) if **delay == default_delay()));
+}
@@ -0,0 +1,209 @@
+use super::*;
+
+/// how many cells are in a ram module
+const RAM_SIZE: usize = 8;
+
+/// label a stored marble for the inspector
+fn stored_label(marble: &Option<Marble>) -> String {
+    marble.map_or_else(|| "empty".to_string(), |m| m.to_string())
+}
+
+/// holds onto the last marble that went into its write input and fires a copy of it whenever
+/// something goes into its read input
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Latch {
+    stored: Option<Marble>,
+    #[serde(default = "default_delay")]
+    delay: f32,
+}
+
+impl Default for Latch {
+    fn default() -> Self {
+        Latch { stored: Default::default(), delay: default_delay() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Latch {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Large)
+                // write, read
+                .with_input_rotations([150.0, -150.0].into_iter(), 0.0)
+                .with_output_rotations([0.0].into_iter(), 0.0),
+            name: "Latch".into(),
+            identifier: "latch.memory".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state.iter().any(Option::is_some) {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if let Some(marble) = state.input_state[0].take() {
+            self.stored = Some(marble);
+        }
+        if state.input_state[1].take().is_some() {
+            events.send(FireMarble(self.stored.unwrap_or(Marble::bit(false))));
+        }
+        events.send(UpdateIndicatorColors);
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![ModuleParam::new("Delay", Param::Delay(&mut self.delay))]
+    }
+
+    fn debug_ui(&mut self, ui: &mut Ui, _: &mut ModuleEventSender, _: &ModuleState) {
+        ui.label(format!("Stored: {}", stored_label(&self.stored)));
+        if ui.button("Clear").clicked() {
+            self.stored = None;
+        }
+    }
+}
+
+/// counts the marbles that go into it, fires the count when something goes into its read input
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Counter {
+    count: i32,
+    #[serde(default = "default_delay")]
+    delay: f32,
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Counter { count: Default::default(), delay: default_delay() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Counter {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Block)
+                // count, read, reset
+                .with_input_rotations([180.0, 90.0, -90.0].into_iter(), 0.0)
+                .with_output_rotations([0.0].into_iter(), 0.0),
+            name: "Counter".into(),
+            identifier: "counter.memory".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state.iter().any(Option::is_some) {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if state.input_state[0].take().is_some() {
+            self.count = self.count.wrapping_add(1);
+        }
+        if state.input_state[1].take().is_some() {
+            events.send(FireMarble(Marble::num(self.count)));
+        }
+        if state.input_state[2].take().is_some() {
+            self.count = 0;
+        }
+        events.send(UpdateIndicatorColors);
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![ModuleParam::new("Delay", Param::Delay(&mut self.delay))]
+    }
+
+    fn debug_ui(&mut self, ui: &mut Ui, _: &mut ModuleEventSender, _: &ModuleState) {
+        ui.label(format!("Count: {}", self.count));
+        if ui.button("Reset").clicked() {
+            self.count = 0;
+        }
+    }
+}
+
+/// a few cells of memory, the address input picks the cell that the write and read inputs use
+#[derive(Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Ram {
+    address: usize,
+    cells: Vec<Option<Marble>>,
+    #[serde(default = "default_delay")]
+    delay: f32,
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Self { address: 0, cells: vec![None; RAM_SIZE], delay: default_delay() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Ram {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Block)
+                // address, write, read
+                .with_input_rotations([135.0, 180.0, -135.0].into_iter(), 0.0)
+                .with_output_rotations([0.0].into_iter(), 0.0),
+            name: "RAM".into(),
+            identifier: "ram.memory".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state.iter().any(Option::is_some) {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        // a save could have any number of cells in it
+        if self.cells.is_empty() {
+            self.cells.resize(RAM_SIZE, None);
+        }
+        if let Some(marble) = state.input_state[0].take() {
+            self.address = marble.get_val().rem_euclid(self.cells.len() as i32) as usize;
+        }
+        let address = self.address % self.cells.len();
+        if let Some(marble) = state.input_state[1].take() {
+            self.cells[address] = Some(marble);
+        }
+        if state.input_state[2].take().is_some() {
+            events.send(FireMarble(self.cells[address].unwrap_or(Marble::num(0))));
+        }
+        events.send(UpdateIndicatorColors);
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![ModuleParam::new("Delay", Param::Delay(&mut self.delay))]
+    }
+
+    fn debug_ui(&mut self, ui: &mut Ui, _: &mut ModuleEventSender, _: &ModuleState) {
+        ui.label(format!("Address: {}", self.address));
+        for (i, cell) in self.cells.iter().enumerate() {
+            let marker = if i == self.address { ">" } else { " " };
+            ui.monospace(format!("{marker} {i}: {}", stored_label(cell)));
+        }
+        if ui.button("Clear").clicked() {
+            self.cells.fill(None);
+        }
+    }
+}
+
+#[test]
+fn test_config_undo_keeps_count() {
+    // changing the delay and putting it back shouldnt touch what its counted so far
+    let mut counter = Counter { count: 3, ..default() };
+    let before = config::param_values(&mut counter);
+    counter.delay = 1.0;
+    counter.count = 5;
+    config::set_param_values(&mut counter, &before);
+    assert_eq!(counter.delay, default_delay());
+    assert_eq!(counter.count, 5);
+}
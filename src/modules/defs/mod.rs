@@ -1,15 +1,25 @@
 use std::sync::Arc;
 
 use crate::*;
-use strum::IntoEnumIterator;
 
 use crate::modules::*;
 use crate::modules::event::ModuleUpdate::*;
-use strum_macros::EnumIter;
 use serde::{Serialize, Deserialize};
 
+/// arithmetic: modules that do math on number marbles
+pub mod arithmetic;
 /// basic: asic modules that do standard stuff
 pub mod basic;
+/// level: the inputs and outputs of a level
+pub mod level;
+/// logic: logic gates that operate on bits
+pub mod logic;
+/// memory: modules that remember things
+pub mod memory;
+/// scripted: modules written in lua, loaded from `data/modules` by the game
+pub mod scripted;
+/// teleporter: modules that send marbles somewhere else without them flying there
+pub mod teleporter;
 
 #[derive(Component)]
 pub struct ModuleComponent {
@@ -20,59 +30,53 @@ pub struct ModuleComponent {
 impl ModuleComponent {
     pub fn new(ty: ModuleType) -> Self {
         Self {
+            module: ty.get_module(),
             ty,
-            module: ty.get_module()
         }
     }
 }
 
-#[derive(EnumIter, Clone, Copy, Debug, Serialize, Deserialize)]
-pub enum ModuleType {
-    Basic,
+/// register every module that comes with the game
+fn register_builtin(registry: &mut ModuleRegistry) {
+    registry.register_default::<basic::Basic>(Some("Basic"));
+    registry.register_default::<basic::Switch>(Some("Basic"));
+    registry.register_default::<level::LevelInput>(None);
+    registry.register_default::<level::LevelOutput>(None);
+    registry.register_default::<logic::And>(Some("Logic"));
+    registry.register_default::<logic::Or>(Some("Logic"));
+    registry.register_default::<logic::Xor>(Some("Logic"));
+    registry.register_default::<logic::Not>(Some("Logic"));
+    registry.register_default::<arithmetic::Adder>(Some("Arithmetic"));
+    registry.register_default::<arithmetic::Subtractor>(Some("Arithmetic"));
+    registry.register_default::<arithmetic::Comparator>(Some("Arithmetic"));
+    registry.register_default::<arithmetic::Incrementer>(Some("Arithmetic"));
+    registry.register_default::<arithmetic::Constant>(Some("Arithmetic"));
+    registry.register_default::<memory::Latch>(Some("Memory"));
+    registry.register_default::<memory::Counter>(Some("Memory"));
+    registry.register_default::<memory::Ram>(Some("Memory"));
+    registry.register_default::<teleporter::Sender>(Some("Teleporters"));
+    registry.register_default::<teleporter::Receiver>(Some("Teleporters"));
 }
 
-impl ModuleType {
-    fn info(self) -> &'static ModuleInfo {
-        unsafe {
-            MODULE_INFO.get(self as usize).unwrap_or_else(|| {
-                error!("Could not get ModuleInfo for ModuleType! Maybe it is unititialized?");
-                &MODULE_INFO[0]
-            })
-        }
-    }
-    /// return instructions on spawning this module
-    pub fn spawn_instructions(&self) -> &'static SpawnInstructions {
-        &self.info().instructions
-    }
-    /// the name of the module
-    pub fn get_name(&self) -> &'static str {
-        self.info().name
-    }
-    /// the identifier of the module
-    pub fn get_identifier(&self) -> &'static str {
-        self.info().identifier
-    }
-    /// get the module
-    pub fn get_module(&self) -> Box<dyn Module> {
-        get_module(*self)
+impl ModuleRegistry {
+    /// the modules built into the game, without any lua ones
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::default();
+        register_builtin(&mut registry);
+        registry
     }
-}
 
-pub(super) static mut MODULES: Vec<Box<dyn super::Module>> = vec![];
-pub(super) static mut MODULE_INFO: Vec<ModuleInfo> = vec![];
+    /// the built in modules and the lua ones in `modules_dir`, usually [`scripted::MODULES_DIR`]
+    pub fn with_scripts(modules_dir: impl AsRef<std::path::Path>) -> Self {
+        let mut registry = Self::with_builtin();
+        scripted::load_scripts(&mut registry, modules_dir.as_ref());
+        registry
+    }
 
-pub fn init_modules() {
-    unsafe {
-        for module in ModuleType::iter() {
-            MODULES.push(get_module(module));
-            MODULE_INFO.push(module.get_module().info());
-        }
+    /// put every module that comes with the game, lua ones from `modules_dir` included, in front of
+    /// whatever is registered already, so packs that got added before the game keep theirs
+    pub fn add_builtin(&mut self, modules_dir: impl AsRef<std::path::Path>) {
+        let packs = std::mem::replace(self, Self::with_scripts(modules_dir));
+        self.append(packs);
     }
 }
-
-fn get_module(module: ModuleType) -> Box<dyn Module> {
-    use ModuleType::*;
-    Box::new(match module {
-        Basic => basic::Basic::default(),
-    })
-}
\ No newline at end of file
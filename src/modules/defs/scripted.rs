@@ -0,0 +1,210 @@
+use std::{ fs, path::Path, sync::{ Arc, Mutex } };
+
+use mlua::{ Function, RegistryKey, Table, prelude::{ LuaError, LuaResult } };
+
+use super::*;
+
+/// where the game loads lua modules from
+pub const MODULES_DIR: &str = "data/modules";
+
+/// helpers every module file gets, so they dont have to build the action tables themselves
+const PRELUDE: &str = r#"
+function fire(marble, output, power)
+    return { fire = marble, output = output or 1, power = power or 1 }
+end
+function callback(secs)
+    return { callback = secs }
+end
+function take(input)
+    return { take = input }
+end
+function teleport(marble, channel)
+    return { teleport = marble, channel = channel }
+end
+"#;
+
+/// the functions of a loaded module, kept in the lua registry. it goes in the modules registration
+/// so every registry has its own lua for its modules to run in
+struct Script {
+    lua: Arc<Mutex<mlua::Lua>>,
+    update: RegistryKey,
+    callback: Option<RegistryKey>,
+}
+
+/// load every module in `dir` into the registry, files with mistakes in them get logged and left
+/// out. theyve all got the same lua to run in
+pub(super) fn load_scripts(registry: &mut ModuleRegistry, dir: &Path) {
+    let shared = Arc::new(Mutex::new(mlua::Lua::new()));
+    let lua = shared.lock().unwrap();
+    if let Err(e) = lua.load(PRELUDE).exec() {
+        error!("couldnt set up lua modules: {e}");
+        return;
+    }
+
+    let mut paths = fs
+        ::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "lua"))
+        .collect::<Vec<_>>();
+    // so ids come out the same every time
+    paths.sort();
+
+    let mut loaded = 0;
+    for path in paths {
+        match load_script(&lua, &shared, &path, registry) {
+            Ok(()) => loaded += 1,
+            Err(e) => error!("couldnt load module {path:?}: {e}"),
+        }
+    }
+    info!("Loaded {loaded} lua module(s)");
+}
+
+/// run a module file, read back the `module` table it filled in and register it
+fn load_script(
+    lua: &mlua::Lua,
+    shared: &Arc<Mutex<mlua::Lua>>,
+    path: &Path,
+    registry: &mut ModuleRegistry
+) -> Result<(), LocatedError> {
+    let module = lua.create_table()?;
+    lua.globals().set("module", module.clone())?;
+    lua.load(&fs::read_to_string(path)?).exec()?;
+
+    let name: String = module.get("name")?;
+    let identifier: String = module.get("identifier")?;
+    let body = match module.get::<_, Option<String>>("body")?.as_deref() {
+        None | Some("small") => BodyType::Small,
+        Some("large") => BodyType::Large,
+        Some("wide") => BodyType::Wide,
+        Some("block") => BodyType::Block,
+        Some(body) => {
+            return Err(LuaError::RuntimeError(format!("{body} isnt a kind of body")).into());
+        }
+    };
+    let inputs: Option<Vec<f32>> = module.get("inputs")?;
+    let outputs: Option<Vec<f32>> = module.get("outputs")?;
+    let update: Function = module.get("update")?;
+    let callback: Option<Function> = module.get("callback")?;
+
+    let info = ModuleInfo {
+        instructions: SpawnInstructions::from_body(body)
+            .with_input_rotations(inputs.unwrap_or_default(), 0.0)
+            .with_output_rotations(outputs.unwrap_or_default(), 0.0),
+        name: name.into(),
+        identifier: identifier.into(),
+    };
+    let script = Script {
+        lua: shared.clone(),
+        update: lua.create_registry_value(update)?,
+        callback: callback.map(|f| lua.create_registry_value(f)).transpose()?,
+    };
+    if registry.register_with(info, Some("Scripted"), |ty| Box::new(Scripted::new(ty)), script).is_none() {
+        return Err(LuaError::RuntimeError("theres already a module with that identifier".to_string()).into());
+    }
+    Ok(())
+}
+
+/// a module thats defined in lua. `update` and `callback` get a table of whats in each input and
+/// return a list of things to do, see [`PRELUDE`]
+#[derive(Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Scripted {
+    script: ModuleType,
+}
+
+impl Scripted {
+    pub fn new(script: ModuleType) -> Self {
+        Scripted { script }
+    }
+
+    fn run(&self, callback: bool, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        let Some(script) = self.script.data::<Script>() else {
+            return;
+        };
+        let key = if callback { script.callback.as_ref() } else { Some(&script.update) };
+        let Some(key) = key else {
+            return;
+        };
+        let lua = script.lua.lock().unwrap();
+        if let Err(e) = call(&lua, key, events, state) {
+            error!("{} failed: {e}", self.script.get_name());
+        }
+        events.send(UpdateIndicatorColors);
+    }
+}
+
+fn call(
+    lua: &mlua::Lua,
+    key: &RegistryKey,
+    events: &mut ModuleEventSender,
+    state: &mut ModuleState
+) -> LuaResult<()> {
+    let function: Function = lua.registry_value(key)?;
+    let inputs = lua.create_table()?;
+    for (i, marble) in state.input_state.iter().enumerate() {
+        inputs.set(i + 1, *marble)?;
+    }
+    let actions: Option<Vec<Table>> = function.call(inputs)?;
+    for action in actions.unwrap_or_default() {
+        do_action(action, events, state)?;
+    }
+    Ok(())
+}
+
+/// turn one of the tables a script returned into a `ModuleUpdate`
+fn do_action(action: Table, events: &mut ModuleEventSender, state: &mut ModuleState) -> LuaResult<()> {
+    // lua counts from 1
+    let index = |i: usize| {
+        i.checked_sub(1).ok_or_else(|| LuaError::RuntimeError("ports start at 1".to_string()))
+    };
+
+    if let Some(marble) = action.get::<_, Option<Marble>>("fire")? {
+        let output = index(action.get::<_, Option<usize>>("output")?.unwrap_or(1))?;
+        let power = action.get::<_, Option<f32>>("power")?.unwrap_or(1.0);
+        events.send(FireMarbleFrom(marble, output, power));
+    } else if let Some(secs) = action.get::<_, Option<f32>>("callback")? {
+        events.send(Callback(secs));
+    } else if let Some(input) = action.get::<_, Option<usize>>("take")? {
+        if let Some(input) = state.input_state.get_mut(index(input)?) {
+            *input = None;
+        }
+    } else if let Some(marble) = action.get::<_, Option<Marble>>("teleport")? {
+        events.send(Teleport(marble, action.get("channel")?));
+    } else {
+        return Err(LuaError::RuntimeError("thats not something a module can do".to_string()));
+    }
+    Ok(())
+}
+
+#[typetag::serde]
+impl Module for Scripted {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: self.script.spawn_instructions().clone(),
+            name: self.script.get_name().into(),
+            identifier: self.script.get_identifier().into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        self.run(false, events, state);
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        self.run(true, events, state);
+    }
+}
+
+#[test]
+fn test_scripted() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(MODULES_DIR);
+    let registry = ModuleRegistry::with_scripts(dir);
+
+    // saved by identifier, so it comes back as the same module
+    let module = registry.get("doubler.lua").expect("doubler.lua gets loaded");
+    let config = ron::ser::to_string(&module.get_module()).unwrap();
+    let loaded: Box<dyn Module> = registry.scope(|| ron::de::from_str(&config)).unwrap();
+    assert_eq!(&*loaded.info().identifier, "doubler.lua");
+}
@@ -0,0 +1,110 @@
+use super::*;
+
+/// the most channels you can link teleporters on
+const MAX_CHANNEL: i32 = 99;
+
+/// senders hold onto marbles a while longer than most modules, so you can see it go in
+fn default_send_delay() -> f32 {
+    0.5
+}
+
+/// swallows marbles and after a bit sends them to the receiver on the same channel
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Sender {
+    #[serde(default)]
+    channel: i32,
+    #[serde(default = "default_send_delay")]
+    delay: f32,
+}
+
+impl Default for Sender {
+    fn default() -> Self {
+        Sender { channel: 0, delay: default_send_delay() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Sender {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_input_rotations([-180.0].into_iter(), 0.0),
+            name: "Sender".into(),
+            identifier: "sender.teleporter".into(),
+        }
+    }
+
+    fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        events.send(UpdateIndicatorColors);
+
+        if state.input_state[0].is_some() {
+            events.send(Callback(self.delay));
+        }
+    }
+
+    fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState) {
+        if let Some(marble) = state.input_state[0].take() {
+            events.send(Teleport(marble, self.channel));
+            events.send(UpdateIndicatorColors);
+        }
+    }
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![
+            ModuleParam::new("Channel", Param::Int(&mut self.channel, 0..=MAX_CHANNEL)),
+            ModuleParam::new("Delay", Param::Delay(&mut self.delay))
+        ]
+    }
+
+    fn link(&self) -> Option<Link> {
+        Some(Link::Sender(self.channel))
+    }
+
+    fn debug_ui(&mut self, ui: &mut Ui, events: &mut ModuleEventSender, _: &ModuleState) {
+        if ui.button("Teleport Marble!").clicked() {
+            events.send(Teleport(Marble::bit(true), self.channel));
+        }
+    }
+}
+
+/// fires whatever the sender on the same channel sent
+#[derive(Copy, Clone, Component, Serialize, Deserialize, Debug)]
+pub struct Receiver {
+    #[serde(default)]
+    channel: i32,
+    #[serde(default = "default_power")]
+    power: f32,
+}
+
+impl Default for Receiver {
+    fn default() -> Self {
+        Receiver { channel: 0, power: default_power() }
+    }
+}
+
+#[typetag::serde]
+impl Module for Receiver {
+    fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            instructions: SpawnInstructions::from_body(BodyType::Small)
+                .with_output_rotations([0.0].into_iter(), 0.0),
+            name: "Receiver".into(),
+            identifier: "receiver.teleporter".into(),
+        }
+    }
+
+    fn update(&mut self, _: &mut ModuleEventSender, _: &mut ModuleState) {}
+
+    fn callback_update(&mut self, _: &mut ModuleEventSender, _: &mut ModuleState) {}
+
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![
+            ModuleParam::new("Channel", Param::Int(&mut self.channel, 0..=MAX_CHANNEL)),
+            ModuleParam::new("Power", Param::Power(&mut self.power))
+        ]
+    }
+
+    fn link(&self) -> Option<Link> {
+        Some(Link::Receiver(self.channel, self.power))
+    }
+}
@@ -1,8 +1,8 @@
 use bevy::{ prelude::*, ecs::system::SystemParam };
 
 use crate::{
-    engine::{ marble::Marble, module_state::ModuleState, marble_io::FireMarbleEvent },
-    modules::ModuleCallbackTimer,
+    engine::{ marble::Marble, module_state::ModuleState, marble_io::FireMarbleEvent, spawn::EditorId },
+    modules::{ ModuleCallbackTimer, ModuleComponent },
 };
 
 use super::QuerySimple;
@@ -37,15 +37,20 @@ impl<'w, 's> ModuleEventSender<'w, 's> {
 /// Things to change about this module
 pub enum ModuleUpdate {
     FireMarble(Marble),
+    /// fire a marble out of a specific output with some power (`1.0` being normal)
+    FireMarbleFrom(Marble, usize, f32),
     ChangeIndicatorColor(f32),
     UpdateIndicatorColors,
     Callback(f32),
+    /// send a marble to the receiver on this channel
+    Teleport(Marble, i32),
 }
 
 #[derive(SystemParam)]
 pub struct Queries<'w, 's> {
     module_state: QuerySimple<'w, 's, ModuleState>,
     sprite: QuerySimple<'w, 's, Sprite>,
+    module: Query<'w, 's, (Entity, &'static ModuleComponent, &'static EditorId)>,
 }
 
 pub fn do_module_events(
@@ -65,11 +70,33 @@ pub fn do_module_events(
                     crate::engine::marble_io::FireMarbleEvent::new(marble, state.outputs[0], 1.0)
                 );
             }
+            FireMarbleFrom(marble, output, power) => {
+                let Some(&output) = state.outputs.get(output) else {
+                    error!("Tried to fire a marble out of output {output} which doesnt exist");
+                    continue;
+                };
+                marble_event.send(
+                    crate::engine::marble_io::FireMarbleEvent::new(marble, output, power)
+                );
+            }
             ChangeIndicatorColor(_) => todo!(),
             UpdateIndicatorColors => update_indicator_colors(state, &mut query.sprite),
             Callback(ticks) => {
                 commands.entity(entity).insert(ModuleCallbackTimer::new(ticks));
             }
+            Teleport(marble, channel) => {
+                let receiver = super::receivers(channel, query.module.iter()).first().copied();
+                let Some((receiver, power)) = receiver else {
+                    warn!("Nothing is listening on channel {channel}, the marble is lost");
+                    continue;
+                };
+                let Some(&output) = query.module_state.get(receiver).ok().and_then(|s| s.outputs.first()) else {
+                    continue;
+                };
+                marble_event.send(
+                    crate::engine::marble_io::FireMarbleEvent::new(marble, output, power)
+                );
+            }
         }
     }
 }
@@ -43,6 +43,9 @@ impl Instruction {
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct SpawnInstructions {
     pub body: BodyType,
+    /// rotation of the body, only really matters if its not round
+    #[serde(default)]
+    pub rotation: f32,
     pub inputs: Vec<Instruction>,
     pub outputs: Vec<Instruction>,
 }
@@ -62,7 +65,7 @@ impl SpawnInstructions {
             .map(|r| {
                 Instruction::new(
                     Vec3::ZERO,
-                    self.body.offset() + 1.0 + d_offset,
+                    self.body.offset_at(r.to_radians()) + 1.0 + d_offset,
                     r.to_radians()
                 )
             })
@@ -80,7 +83,7 @@ impl SpawnInstructions {
             .map(|r| {
                 Instruction::new(
                     Vec3::ZERO,
-                    self.body.offset() + 0.5 + d_offset,
+                    self.body.offset_at(r.to_radians()) + 0.5 + d_offset,
                     r.to_radians()
                 )
             })
@@ -8,30 +8,51 @@ pub use instructions::*;
 pub mod defs;
 pub use defs::*;
 
+/// every kind of module the game knows about
+pub mod registry;
+pub use registry::{ ModuleType, ModuleRegistry };
+
 mod event;
 
-use std::time::Duration;
+/// settings modules can expose in the config panel
+pub mod config;
+pub use config::{ ModuleParam, Param, ModuleConfigChanged, default_delay, default_power };
+
+use std::sync::Arc;
+
 use bevy::{prelude::*, ecs::component::TableStorage };
 use derive_more::{ Deref, DerefMut };
 
-use crate::{ engine::{ module_state::ModuleState, marble::Marble } };
+use crate::{
+    engine::{
+        module_state::ModuleState,
+        marble::Marble,
+        sim::{ self, SimClock, AppSimExt, TickStage },
+        spawn::EditorId,
+    },
+};
 
-pub use self::event::{ ModuleEventSender, ModuleEvent };
+pub use self::event::{ ModuleEventSender, ModuleEvent, ModuleUpdate };
 
 pub fn app(app: &mut App) {
-    app.add_event::<ModuleEvent>().add_system_set_to_stage(
-        CoreStage::Update,
-        SystemSet::new()
-            .with_system(update_modules.label("modules::update_modules"))
-            .with_system(update_module_callbacks.label("modules::update_modules"))
-            .with_system(event::do_module_events.after("modules::update_modules"))
-    );
+    app.init_resource::<ModuleRegistry>()
+        .add_event::<ModuleEvent>()
+        .add_event::<ModuleConfigChanged>()
+        .add_tick_system_set(
+            TickStage::Logic,
+            SystemSet::new()
+                .after("marble_io::update_inputs")
+                .with_system(update_modules.label("modules::update_modules"))
+                .with_system(update_module_callbacks.label("modules::update_modules"))
+                .with_system(event::do_module_events.after("modules::update_modules"))
+        );
 }
 
+#[derive(Clone)]
 pub struct ModuleInfo {
-    instructions: SpawnInstructions,
-    name: &'static str,
-    identifier: &'static str,
+    pub instructions: SpawnInstructions,
+    pub name: Arc<str>,
+    pub identifier: Arc<str>,
 }
 
 #[typetag::serde(tag = "type")]
@@ -40,6 +61,16 @@ pub trait Module : Component<Storage = TableStorage> + std::fmt::Debug + dyn_clo
     /// function that runs to update this module
     fn update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState);
     fn callback_update(&mut self, events: &mut ModuleEventSender, state: &mut ModuleState);
+
+    /// the settings this module has, shown in the config panel and saved along with it
+    fn params(&mut self) -> Vec<ModuleParam<'_>> {
+        vec![]
+    }
+    
+    /// if this is one end of a teleporter link, which end and what channel
+    fn link(&self) -> Option<Link> {
+        None
+    }
     
     #[allow(unused_variables)]
     fn debug_ui(&mut self, ui: &mut Ui, events: &mut ModuleEventSender, state: &ModuleState) {
@@ -49,6 +80,50 @@ pub trait Module : Component<Storage = TableStorage> + std::fmt::Debug + dyn_clo
     }
 }
 
+/// one end of a teleporter link, marbles go from senders to receivers on the same channel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Link {
+    Sender(i32),
+    /// the power it fires marbles with
+    Receiver(i32, f32),
+}
+
+impl Link {
+    pub fn channel(self) -> i32 {
+        match self {
+            Link::Sender(channel) | Link::Receiver(channel, _) => channel,
+        }
+    }
+
+    /// whether marbles go between these two
+    pub fn pairs_with(self, other: Link) -> bool {
+        use Link::*;
+        matches!((self, other), (Sender(_), Receiver(..)) | (Receiver(..), Sender(_))) &&
+            self.channel() == other.channel()
+    }
+}
+
+/// every receiver on `channel` along with the power it fires with, the first one being the one
+/// that actually gets the marbles. thats the one placed first (lowest [`EditorId`]) so which one it
+/// is never comes down to what order the entities happen to be in
+pub fn receivers<'a>(
+    channel: i32,
+    modules: impl IntoIterator<Item = (Entity, &'a ModuleComponent, &'a EditorId)>
+) -> Vec<(Entity, f32)> {
+    let mut receivers = modules
+        .into_iter()
+        .filter_map(|(entity, module, id)| match module.module.link() {
+            Some(Link::Receiver(c, power)) if c == channel => Some((id.0, entity, power)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    receivers.sort_by_key(|(id, ..)| *id);
+    receivers
+        .into_iter()
+        .map(|(_, entity, power)| (entity, power))
+        .collect()
+}
+
 impl Clone for Box<dyn Module> {
     fn clone(&self) -> Self {
         dyn_clone::clone_box(&**self)
@@ -60,12 +135,13 @@ type QuerySimple<'w, 's, T> = Query<'w, 's, &'static mut T>;
 // type QueryEntity<'w, 's, W> = Query<'w, 's, bevy::prelude::Entity, bevy::prelude::With<W>>;
 
 /// "i want to do something after x second(s) pls help"
+/// counts down in sim ticks rather than real time so machines run the same every time
 #[derive(Deref, DerefMut, Component)]
-pub struct ModuleCallbackTimer(Timer);
+pub struct ModuleCallbackTimer(u64);
 
 impl ModuleCallbackTimer {
-    pub fn new(ticks: f32) -> Self {
-        ModuleCallbackTimer(Timer::new(Duration::from_secs_f32(ticks), TimerMode::Once))
+    pub fn new(secs: f32) -> Self {
+        ModuleCallbackTimer(sim::ticks(secs))
     }
 }
 
@@ -73,14 +149,17 @@ pub fn update_module_callbacks(
     mut commands: Commands,
     mut timers: Query<(&mut ModuleComponent, Entity, &mut ModuleCallbackTimer, &mut ModuleState)>,
     events: EventWriter<ModuleEvent>,
-    time: Res<Time>,
+    clock: Res<SimClock>,
 ) {
+    if !clock.ticked() {
+        return;
+    }
     let mut events = ModuleEventSender::new(events);
     for (mut module, entity, mut timer, mut state) in timers.iter_mut() {
         events.entity(entity);
-        timer.tick(time.delta());
+        **timer = timer.saturating_sub(clock.delta());
         
-        if timer.finished() {
+        if **timer == 0 {
             module.module.callback_update(&mut events, &mut state);
             commands.entity(entity).remove::<ModuleCallbackTimer>();
         }
@@ -0,0 +1,293 @@
+use std::{ any::Any, cell::RefCell, collections::BTreeSet, fmt, hash::{ Hash, Hasher }, sync::{ Arc, Mutex } };
+
+use serde::{ Serialize, Serializer, Deserialize, Deserializer, de::{ self, Visitor, EnumAccess, VariantAccess } };
+
+use super::*;
+
+/// a kind of module, points at its entry in the [`ModuleRegistry`] it came from
+#[derive(Clone)]
+pub struct ModuleType(Arc<Registration>);
+
+struct Registration {
+    info: ModuleInfo,
+    /// which section of the palette it goes in, `None` to leave it out
+    section: Option<&'static str>,
+    make: fn(ModuleType) -> Box<dyn Module>,
+    /// anything else the module needs kept along with it, lua modules keep their functions here
+    data: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl ModuleType {
+    /// return instructions on spawning this module
+    pub fn spawn_instructions(&self) -> &SpawnInstructions {
+        &self.0.info.instructions
+    }
+    /// the name of the module
+    pub fn get_name(&self) -> &str {
+        &self.0.info.name
+    }
+    /// the identifier of the module
+    pub fn get_identifier(&self) -> &str {
+        &self.0.info.identifier
+    }
+    /// get the module
+    pub fn get_module(&self) -> Box<dyn Module> {
+        (self.0.make)(self.clone())
+    }
+    /// whatever it was registered with using [`ModuleRegistry::register_with`]
+    pub fn data<T: Any>(&self) -> Option<&T> {
+        self.0.data.as_deref()?.downcast_ref()
+    }
+}
+
+impl PartialEq for ModuleType {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_identifier() == other.get_identifier()
+    }
+}
+
+impl Eq for ModuleType {}
+
+impl Hash for ModuleType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_identifier().hash(state);
+    }
+}
+
+impl fmt::Debug for ModuleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ModuleType({})", self.get_identifier())
+    }
+}
+
+/// the built in modules by the names they had as enum variants
+const LEGACY_NAMES: [(&str, &str); 18] = [
+    ("Basic", "basic.module"),
+    ("Switch", "switch.module"),
+    ("LevelInput", "input.level"),
+    ("LevelOutput", "output.level"),
+    ("And", "and.logic"),
+    ("Or", "or.logic"),
+    ("Xor", "xor.logic"),
+    ("Not", "not.logic"),
+    ("Adder", "add.arithmetic"),
+    ("Subtractor", "sub.arithmetic"),
+    ("Comparator", "compare.arithmetic"),
+    ("Incrementer", "inc.arithmetic"),
+    ("Constant", "const.arithmetic"),
+    ("Latch", "latch.memory"),
+    ("Counter", "counter.memory"),
+    ("Ram", "ram.memory"),
+    ("Sender", "sender.teleporter"),
+    ("Receiver", "receiver.teleporter"),
+];
+
+/// serde only takes variant names that live forever, so each identifier gets leaked the first time
+/// one is written. thats once per identifier, not once per registry
+fn static_identifier(identifier: &str) -> &'static str {
+    static WRITTEN: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut written = WRITTEN.lock().unwrap();
+    if let Some(&identifier) = written.get(identifier) {
+        return identifier;
+    }
+    let leaked: &'static str = Box::leak(Box::<str>::from(identifier));
+    written.insert(leaked);
+    leaked
+}
+
+// written as a unit variant named after the identifier, which is what the enum looked like, so
+// saves from before the registry still load
+impl Serialize for ModuleType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_variant("ModuleType", 0, static_identifier(self.get_identifier()))
+    }
+}
+
+impl<'de> Deserialize<'de> for ModuleType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("ModuleType", &[], ModuleTypeVisitor)
+    }
+}
+
+struct ModuleTypeVisitor;
+
+impl ModuleTypeVisitor {
+    fn find<E: de::Error>(name: &str) -> Result<ModuleType, E> {
+        SCOPE.with(|scope| {
+            let scope = scope.borrow();
+            let Some(registry) = scope.as_ref() else {
+                return Err(E::custom("modules can only be read inside of a ModuleRegistry::scope"));
+            };
+            registry.get_by_name(name).ok_or_else(|| E::custom(format!("theres no module called {name}")))
+        })
+    }
+}
+
+impl<'de> Visitor<'de> for ModuleTypeVisitor {
+    type Value = ModuleType;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the identifier of a module")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<ModuleType, E> {
+        Self::find(name)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<ModuleType, A::Error> {
+        let (Name(name), variant) = data.variant()?;
+        // lua modules used to be `Scripted("identifier")`
+        if name == "Scripted" {
+            return Self::find(&variant.newtype_variant::<String>()?);
+        }
+        variant.unit_variant()?;
+        Self::find(&name)
+    }
+}
+
+/// the name of a variant, which formats like ron only hand out as an identifier
+pub(crate) struct Name(pub String);
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NameVisitor;
+        impl<'de> Visitor<'de> for NameVisitor {
+            type Value = Name;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an identifier")
+            }
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Name, E> {
+                Ok(Name(name.to_string()))
+            }
+        }
+        deserializer.deserialize_identifier(NameVisitor)
+    }
+}
+
+thread_local! {
+    /// the registry modules get looked up in while theyre being deserialized, see
+    /// [`ModuleRegistry::scope`]
+    static SCOPE: RefCell<Option<ModuleRegistry>> = RefCell::new(None);
+}
+
+/// every kind of module an app knows about, by identifier and in the order they were registered.
+/// each app has its own, the game puts [`ModuleRegistry::with_builtin`] and the lua modules in and
+/// packs add to it
+#[derive(Resource, Clone, Default, Debug)]
+pub struct ModuleRegistry {
+    modules: Vec<ModuleType>,
+}
+
+impl ModuleRegistry {
+    /// add a kind of module, `make` gets called whenever a fresh one is needed. gives `None` if
+    /// theres already a module with its identifier
+    pub fn register(
+        &mut self,
+        info: ModuleInfo,
+        section: Option<&'static str>,
+        make: fn(ModuleType) -> Box<dyn Module>
+    ) -> Option<ModuleType> {
+        self.add(Registration { info, section, make, data: None })
+    }
+
+    /// like [`ModuleRegistry::register`] but keeps `data` along with it, modules can get it back
+    /// out of their type with [`ModuleType::data`]
+    pub fn register_with<T: Any + Send + Sync>(
+        &mut self,
+        info: ModuleInfo,
+        section: Option<&'static str>,
+        make: fn(ModuleType) -> Box<dyn Module>,
+        data: T
+    ) -> Option<ModuleType> {
+        self.add(Registration { info, section, make, data: Some(Arc::new(data)) })
+    }
+
+    /// add a module thats made with [`Default`]
+    pub fn register_default<T: Module + Default>(&mut self, section: Option<&'static str>) -> Option<ModuleType> {
+        fn make<T: Module + Default>(_: ModuleType) -> Box<dyn Module> {
+            Box::new(T::default())
+        }
+        self.register(T::default().info(), section, make::<T>)
+    }
+
+    fn add(&mut self, registration: Registration) -> Option<ModuleType> {
+        if self.get(&registration.info.identifier).is_some() {
+            return None;
+        }
+        let module = ModuleType(Arc::new(registration));
+        self.modules.push(module.clone());
+        Some(module)
+    }
+
+    /// add every module in `other` that isnt in here already, after the ones that are
+    pub fn append(&mut self, other: ModuleRegistry) {
+        for module in other.modules {
+            if self.get(module.get_identifier()).is_none() {
+                self.modules.push(module);
+            }
+        }
+    }
+
+    /// the module with this identifier, if theres one
+    pub fn get(&self, identifier: &str) -> Option<ModuleType> {
+        self.modules
+            .iter()
+            .find(|m| m.get_identifier() == identifier)
+            .cloned()
+    }
+
+    /// like [`ModuleRegistry::get`] but also takes what modules were called back when they were an
+    /// enum
+    pub fn get_by_name(&self, name: &str) -> Option<ModuleType> {
+        let identifier = LEGACY_NAMES
+            .iter()
+            .find(|(old, _)| *old == name)
+            .map_or(name, |(_, identifier)| identifier);
+        self.get(identifier)
+    }
+
+    /// every module there is
+    pub fn all(&self) -> &[ModuleType] {
+        &self.modules
+    }
+
+    /// the modules that go in the palette, grouped into sections in the order each first showed up
+    pub fn sections(&self) -> Vec<(&'static str, Vec<ModuleType>)> {
+        let mut sections: Vec<(&'static str, Vec<ModuleType>)> = vec![];
+        for module in self.modules.iter() {
+            let Some(name) = module.0.section else {
+                continue;
+            };
+            match sections.iter_mut().find(|(section, _)| *section == name) {
+                Some((_, modules)) => modules.push(module.clone()),
+                None => sections.push((name, vec![module.clone()])),
+            }
+        }
+        sections
+    }
+
+    /// run `f` with this as the registry anything deserialized in it looks its modules up in,
+    /// since serde has no way of handing it over. anything with a [`ModuleType`] in it (saves, the
+    /// clipboard, the configs of lua modules) has to be read inside of one of these
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let outer = SCOPE.with(|scope| scope.replace(Some(self.clone())));
+        let result = f();
+        SCOPE.with(|scope| scope.replace(outer));
+        result
+    }
+}
+
+#[test]
+fn test_legacy_names() {
+    let registry = ModuleRegistry::with_builtin();
+
+    let basic: ModuleType = registry.scope(|| ron::de::from_str("Basic")).unwrap();
+    assert_eq!(basic.get_identifier(), "basic.module");
+    let written = ron::ser::to_string(&basic).unwrap();
+    assert_eq!(registry.scope(|| ron::de::from_str::<ModuleType>(&written)).unwrap(), basic);
+
+    // another app with nothing registered doesnt see any of it
+    let empty = ModuleRegistry::default();
+    assert!(empty.get("basic.module").is_none());
+    assert!(empty.scope(|| ron::de::from_str::<ModuleType>(&written)).is_err());
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use crate::{
+    *,
+    graphics::atlas::AtlasRegistry,
+    modules::ModuleRegistry,
+    game::level::LevelDirs,
+};
+
+/// a bunch of modules from outside the game, along with anything they need. add one to the app
+/// with [`PackPlugin`], `examples/pack.rs` is a small one
+pub trait ModulePack: Send + Sync + 'static {
+    /// register every module in the pack, the section each one gets registered with is where it
+    /// shows up in the palette
+    fn modules(&self, registry: &mut ModuleRegistry);
+
+    /// add the atlases the packs modules draw with
+    #[allow(unused_variables)]
+    fn atlases(&self, atlases: &mut AtlasRegistry) {}
+
+    /// folders of lua levels to load along with the games own
+    fn level_dirs(&self) -> Vec<PathBuf> {
+        vec![]
+    }
+}
+
+/// adds a [`ModulePack`] to the game, before or after [`MarbleMachinePlugin`]
+pub struct PackPlugin<P: ModulePack>(pub P);
+
+impl<P: ModulePack> Plugin for PackPlugin<P> {
+    fn build(&self, app: &mut App) {
+        self.0.modules(&mut app.world.get_resource_or_insert_with(ModuleRegistry::default));
+
+        self.0.atlases(&mut app.world.get_resource_or_insert_with(AtlasRegistry::default));
+
+        let dirs = self.0.level_dirs();
+        app.world.get_resource_or_insert_with(LevelDirs::default).extend(dirs);
+    }
+}
+
+#[test]
+fn test_pack() {
+    use crate::modules::{ basic::Basic, Module, ModuleInfo };
+
+    /// a copy of the basic module under another name, along with the example packs levels and a
+    /// folder that isnt there
+    struct TestPack;
+
+    impl ModulePack for TestPack {
+        fn modules(&self, registry: &mut ModuleRegistry) {
+            let info = ModuleInfo { name: "Copy".into(), identifier: "copy.test".into(), ..Basic::default().info() };
+            registry.register(info, Some("Test"), |_| Box::new(Basic::default()));
+        }
+
+        fn level_dirs(&self) -> Vec<PathBuf> {
+            vec![PathBuf::from("examples/pack/levels"), PathBuf::from("examples/pack/missing")]
+        }
+    }
+
+    // the pack goes in before the game does, which shouldnt lose it its modules
+    let mut app = App::new();
+    app.add_plugin(PackPlugin(TestPack));
+    app.world.resource_mut::<ModuleRegistry>().add_builtin(crate::modules::scripted::MODULES_DIR);
+
+    let registry = app.world.resource::<ModuleRegistry>();
+    assert!(registry.get("basic.module").is_some());
+    assert!(registry.get("copy.test").is_some());
+    let sections = registry.sections();
+    assert_eq!(sections.first().map(|(section, _)| *section), Some("Basic"));
+    assert_eq!(sections.last().map(|(section, _)| *section), Some("Test"));
+
+    // the missing folder gets skipped instead of taking the games own levels down with it
+    let lua = mlua::Lua::new();
+    let levels = crate::game::level::load_dirs(&lua, app.world.resource::<LevelDirs>(), registry);
+    let names = levels.iter().map(|level| level.name.as_str()).collect::<Vec<_>>();
+    assert!(names.contains(&"Start"));
+    assert!(names.contains(&"Split"));
+}
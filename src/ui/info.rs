@@ -25,9 +25,10 @@ pub fn ui(
 ) {
     let ctx = egui_ctx.ctx_mut();
     
-    let Some(module) = hovered.or_else(||
-        selected.selected.map(|e| {
-            q_module.get(e).unwrap().ty
+    let Some(module) = hovered.clone().or_else(||
+        selected.selected.and_then(|e| {
+            // pieces are selectable too but arent modules
+            q_module.get(e).ok().map(|m| m.ty.clone())
         })
     ) else {
         return;
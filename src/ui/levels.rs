@@ -0,0 +1,136 @@
+use crate::{
+    *,
+    game::{
+        level::{ CurrentLevel, Levels, LoadLevel },
+        progress::Progress,
+        save_load::{ MachineQuery, SaveData },
+        verify::VerifyTask,
+    },
+    modules::ModuleRegistry,
+};
+use bevy_egui::*;
+use egui::*;
+use iyes_loopless::prelude::*;
+
+/// the level select screen, pick one on the left and read about it on the right
+pub fn level_select(
+    mut commands: Commands,
+    mut egui_context: ResMut<EguiContext>,
+    levels: Option<NonSend<Levels<'static>>>,
+    progress: Res<Progress>,
+    mut chosen: Local<usize>,
+    mut load_events: EventWriter<LoadLevel>,
+) {
+    let Some(levels) = levels else {
+        return;
+    };
+
+    CentralPanel::default().show(egui_context.ctx_mut(), |ui| {
+        ui.heading("Levels");
+        ui.separator();
+
+        ui.columns(2, |columns| {
+            for (i, level) in levels.iter().enumerate() {
+                let check = if progress.completed(&level.id) { "✔" } else { "  " };
+                columns[0].selectable_value(&mut *chosen, i, format!("{check} {}", level.name));
+            }
+
+            let Some(level) = levels.get(*chosen) else {
+                return;
+            };
+            let ui = &mut columns[1];
+            ui.heading(level.name.as_str());
+            ui.label(format!("{} input(s), {} output(s)", level.inputs.len(), level.outputs.len()));
+            if let Some(progress) = progress.get(&level.id) && progress.completed {
+                let best = |n: Option<usize>| n.map_or("-".to_string(), |n| n.to_string());
+                ui.label(
+                    format!(
+                        "Completed! best: {} ticks, {} modules",
+                        best(progress.best_ticks),
+                        best(progress.best_modules)
+                    )
+                );
+            }
+            if ui.button(" Play ").clicked() {
+                load_events.send(LoadLevel(*chosen));
+                commands.insert_resource(NextState(GameState::Playing));
+            }
+            ui.separator();
+            ScrollArea::vertical().show(ui, |ui| {
+                ui.label(level.description.as_str());
+            });
+        });
+    });
+}
+
+/// the window for the level being played, read the description again or submit the machine
+#[allow(clippy::too_many_arguments)]
+pub fn level_info(
+    mut commands: Commands,
+    mut egui_context: ResMut<EguiContext>,
+    levels: Option<NonSend<Levels<'static>>>,
+    current: Res<CurrentLevel>,
+    mut progress: ResMut<Progress>,
+    q_machine: MachineQuery,
+    mut result: Local<Option<String>>,
+    mut running: Local<Option<(VerifyTask, usize)>>,
+    registry: Res<ModuleRegistry>,
+) {
+    let Some(level) = current.and_then(|i| levels.as_ref()?.get(i)) else {
+        return;
+    };
+    if current.is_changed() {
+        *result = None;
+        *running = None;
+    }
+
+    // check in on the cases running in the background
+    if let Some((task, modules)) = &*running && let Some(report) = task.poll() {
+        let modules = *modules;
+        *running = None;
+        *result = Some(match report {
+            Ok(report) if report.passed() => {
+                progress.complete(&level.id, report.total_ticks(), modules);
+                if let Err(e) = progress.write() {
+                    error!("couldnt save progress: {e}");
+                }
+                format!("Passed in {} ticks with {modules} modules", report.total_ticks())
+            }
+            Ok(report) => {
+                let passed = report.cases.iter().filter(|c| c.passed).count();
+                format!("Failed, {passed}/{} cases passed", report.cases.len())
+            }
+            Err(e) => {
+                error!("{e}");
+                "Couldnt run the test cases".to_string()
+            }
+        });
+    }
+
+    egui::Window
+        ::new("level")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_TOP, [0.0, 8.0])
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.heading(level.name.as_str());
+                let submit = ui.add_enabled(running.is_none(), egui::Button::new(" submit "));
+                if submit.on_hover_text("Run every test case").clicked() {
+                    let save = SaveData::from(q_machine.snapshot());
+                    let modules = save.modules.len();
+                    *running = Some((VerifyTask::spawn(level, save, &registry), modules));
+                    *result = Some("Running the test cases...".to_string());
+                }
+                if ui.button(" levels ").clicked() {
+                    commands.insert_resource(NextState(GameState::LevelSelect));
+                }
+            });
+            if let Some(result) = &*result {
+                ui.label(result.as_str());
+            }
+            ui.collapsing("Description", |ui| {
+                ui.label(level.description.as_str());
+            });
+        });
+}
@@ -1,5 +1,6 @@
 use crate::*;
 use bevy_egui::{ egui, EguiContext };
+use iyes_loopless::prelude::*;
 
 use self::info::HoveredModule;
 
@@ -9,6 +10,8 @@ pub mod atlas_image;
 pub mod spawning;
 // info panel
 pub mod info;
+// picking levels and the window for the one being played
+pub mod levels;
 pub mod ui;
 
 pub fn app(app: &mut App) {
@@ -16,11 +19,14 @@ pub fn app(app: &mut App) {
         .add_system_set_to_stage(
             CoreStage::Update,
             SystemSet::new()
-                .with_system(info::ui)
-                .with_system(ui::inspector_ui)
+                .with_system(info::ui.run_in_state(GameState::Playing))
+                .with_system(ui::inspector_ui.run_in_state(GameState::Playing))
+                .with_system(ui::blueprint_ui.run_in_state(GameState::Playing))
                 // .with_system(ui::spawning_ui)
-                .with_system(spawning::ui)
-                .with_system(ui::debug_ui)
+                .with_system(spawning::ui.run_in_state(GameState::Playing))
+                .with_system(ui::debug_ui.run_in_state(GameState::Playing))
+                .with_system(levels::level_info.run_in_state(GameState::Playing))
+                .with_system(levels::level_select.run_in_state(GameState::LevelSelect))
         )
         .add_startup_system_to_stage(StartupStage::Startup, set_style);
 }
@@ -1,5 +1,7 @@
 use crate::{
-    modules::{ body::BodyType, ModuleType, SpawnInstructions },
+    modules::{ body::BodyType, ModuleType, ModuleRegistry, SpawnInstructions },
+    game::blueprint::{ Blueprints, SpawnBlueprint },
+    engine::piece::{ PieceKind, PieceInfo, SpawnPiece },
     graphics::atlas::{ basic, AtlasDictionary },
     *,
 };
@@ -23,6 +25,9 @@ impl ImageItem {
 #[derive(Resource)]
 pub struct Images {
     pub body_small: ImageItem,
+    pub body: ImageItem,
+    pub body_wide: ImageItem,
+    pub body_block: ImageItem,
     pub input: ImageItem,
     pub output: ImageItem,
     pub indicator: ImageItem,
@@ -53,6 +58,9 @@ impl FromWorld for Images {
 
         Self {
             body_small: new_atlas!(basic::body_small; .tint(BodyType::Small.color32())),
+            body: new_atlas!(basic::body; .tint(BodyType::Large.color32())),
+            body_wide: new_atlas!(basic::body_wide; .tint(BodyType::Wide.color32())),
+            body_block: new_atlas!(basic::body_block; .tint(BodyType::Block.color32())),
             input: new_atlas! {
                 basic::marble_input;
             },
@@ -71,30 +79,37 @@ pub enum ModuleItem {
     Module {
         module: ModuleType,
     },
+    /// one of the player's blueprints, by name
+    Blueprint(String),
+    /// a bit of geometry, see [`PieceKind`]
+    Piece(PieceKind),
     SectionHeader(&'static str),
 }
 
+/// the pieces, which always go after whatever modules are registered
 #[ctor::ctor]
-static MODULES: Vec<ModuleItem> =  {
-    use crate::modules::ModuleType::*;
-    let item = |module| {
-        ModuleItem::Module {
-            module,
-        }
-    };
-    let header = |name| { ModuleItem::SectionHeader(name) };
+static GEOMETRY: Vec<ModuleItem> =  {
+    let piece = |kind| { ModuleItem::Piece(kind) };
 
-    vec![header("Basic"), item(Basic), item(Basic), item(Basic)]
+    vec![
+        ModuleItem::SectionHeader("Geometry"),
+        piece(PieceKind::Wall), piece(PieceKind::Ramp), piece(PieceKind::Bumper), piece(PieceKind::Funnel),
+    ]
 };
 
 pub const SIZE: Vec2 = Vec2::new(80.0, 80.0);
 
+#[allow(clippy::too_many_arguments)]
 pub fn ui(
     mut egui_context: ResMut<EguiContext>,
     images: Res<Images>,
     // mut windows: ResMut<Windows>,
     mut spawn_modules: EventWriter<spawn::SpawnModule>,
-    mut hovered: ResMut<HoveredModule>
+    mut hovered: ResMut<HoveredModule>,
+    registry: Res<ModuleRegistry>,
+    blueprints: Res<Blueprints>,
+    mut spawn_blueprints: EventWriter<SpawnBlueprint>,
+    mut spawn_pieces: EventWriter<SpawnPiece>
 ) {
     // let Some(window) = windows.get_primary_mut() else { error!("take a guess what the error is"); return };
 
@@ -119,7 +134,25 @@ pub fn ui(
             ui.set_width(width * SIZE.x + spacing);
             // dbg!(width);
             
-            let mut iter = MODULES.iter().peekable();
+            let modules = registry
+                .sections()
+                .into_iter()
+                .flat_map(|(section, modules)| {
+                    std::iter
+                        ::once(ModuleItem::SectionHeader(section))
+                        .chain(modules.into_iter().map(|module| ModuleItem::Module { module }))
+                })
+                .collect::<Vec<_>>();
+            // the players own stuff goes at the end
+            let custom = if blueprints.is_empty() {
+                vec![]
+            } else {
+                std::iter
+                    ::once(ModuleItem::SectionHeader("Custom"))
+                    .chain(blueprints.keys().map(|name| ModuleItem::Blueprint(name.clone())))
+                    .collect()
+            };
+            let mut iter = modules.iter().chain(GEOMETRY.iter()).chain(custom.iter()).peekable();
 
             let mut set = None;
 
@@ -130,42 +163,66 @@ pub fn ui(
 
                 while i < i32::max(width as i32, 1) && let Some(item) = iter.next() {
                     // dbg!(item);
-                    match item {
+                    let (instructions, module) = match item {
                         ModuleItem::Module { module } => {
-                            // dbg!(cursor);
-                            // allocate space
-                            let translate =
-                                cursor + Vec2::X * ((SIZE.x + spacing + 5.0) * (i as f32));
-                            let allocated = size_rect.translate(translate);
-                            // dbg!(allocated.min);
-
-                            // put down the button
-                            let button = ui.put(allocated, Button::new(""));
-
-                            if button.hovered() {
-                                set = Some(module);
-                            }
-                            if button.clicked() {
-                                spawn_modules.send(spawn::SpawnModule::from_type(*module).place());
-                            }
-
-                            // allocate the area to draw the module and throw stuff there
-                            ui.allocate_rect(allocated, Sense::hover()).hovered();
-
-                            let mut new_ui = ui.child_ui(allocated, Layout::default());
-                            recreate_module(&mut new_ui, &images, module.spawn_instructions(), false);
-
-                            i += 1;
+                            (Some(module.spawn_instructions().clone()), Some(module))
                         }
+                        ModuleItem::Blueprint(name) => {
+                            (Some(blueprints.get(name).map(|b| b.preview()).unwrap_or_default()), None)
+                        }
+                        ModuleItem::Piece(_) => (None, None),
                         ModuleItem::SectionHeader(str) => {
                             ui.add(Label::new(*str));
                             break;
                         }
+                    };
+
+                    // dbg!(cursor);
+                    // allocate space
+                    let translate =
+                        cursor + Vec2::X * ((SIZE.x + spacing + 5.0) * (i as f32));
+                    let allocated = size_rect.translate(translate);
+                    // dbg!(allocated.min);
+
+                    // put down the button
+                    let button = ui.put(allocated, Button::new(""));
+
+                    if let Some(module) = module {
+                        if button.hovered() {
+                            set = Some(module);
+                        }
+                        if button.clicked() {
+                            spawn_modules.send(spawn::SpawnModule::from_type(module.clone()).place());
+                        }
+                    } else {
+                        match item {
+                            ModuleItem::Blueprint(name) => if button.on_hover_text(name.as_str()).clicked() {
+                                spawn_blueprints.send(SpawnBlueprint(name.clone()));
+                            }
+                            ModuleItem::Piece(kind) => if button.on_hover_text(kind.name()).clicked() {
+                                spawn_pieces.send(SpawnPiece::new(PieceInfo::new(*kind)).place());
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    // allocate the area to draw the module and throw stuff there
+                    ui.allocate_rect(allocated, Sense::hover()).hovered();
+
+                    let mut new_ui = ui.child_ui(allocated, Layout::default());
+                    match (instructions, item) {
+                        (Some(instructions), _) => {
+                            recreate_module(&mut new_ui, &images, &instructions, false)
+                        }
+                        (None, ModuleItem::Piece(kind)) => recreate_piece(&mut new_ui, *kind),
+                        _ => {}
                     }
+
+                    i += 1;
                 }
             }
 
-            **hovered = set.copied();
+            **hovered = set.cloned();
 
             ui.set_width(ui.min_size().x);
         });
@@ -247,7 +304,9 @@ pub fn recreate_module(
     // spawn body
     let atlas_image = match instructions.body {
         BodyType::Small => &images.body_small,
-        BodyType::Large => todo!(),
+        BodyType::Large => &images.body,
+        BodyType::Wide => &images.body_wide,
+        BodyType::Block => &images.body_block,
     };
     put!(ui_center, *atlas_image);
 
@@ -257,4 +316,22 @@ pub fn recreate_module(
         i.ext -= 2.0;
         put_tf!(i, images.indicator);
     }
-}
\ No newline at end of file
+}
+/// pieces dont have sprites so just draw their outline
+pub fn recreate_piece(ui: &mut Ui, kind: PieceKind) {
+    let rect = ui.max_rect();
+    let [r, g, b, _] = kind.color().as_rgba_f32();
+    let color = Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8);
+    // y goes the other way in egui
+    let to_screen = |p: bevy::prelude::Vec2| rect.center() + Vec2::new(p.x, -p.y) * SCALING * 0.75;
+
+    let painter = ui.painter_at(rect);
+    if kind == PieceKind::Bumper {
+        painter.circle_filled(rect.center(), 4.0 * SCALING * 0.75, color);
+        return;
+    }
+    for line in kind.outline() {
+        let points = line.into_iter().map(to_screen).collect();
+        painter.add(Shape::line(points, Stroke::new(2.0 * SCALING * 0.75, color)));
+    }
+}
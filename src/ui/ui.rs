@@ -2,9 +2,11 @@ use std::f32::consts;
 
 use crate::{
     *,
-    modules::{ ModuleType, ModuleEventSender, ModuleComponent },
-    engine::module_state::ModuleState,
-    game::save_load::{SaveWorld, LoadWorld},
+    modules::{ ModuleType, ModuleEventSender, ModuleComponent, ModuleConfigChanged, Link, config },
+    engine::{ module_state::ModuleState, sim::{ self, SimClock, SimControl }, spawn::EditorId },
+    game::{ save_load::{ SaveWorld, LoadWorld, SaveSlots }, level::{ RunCase, ReceivedMarbles } },
+    interactive::{ clipboard::Clipboard, tracer::TrajectoryOverlay },
+    game::blueprint::{ Blueprints, BlueprintTag, SaveBlueprint, DetachBlueprint },
 };
 use bevy_egui::*;
 use egui::*;
@@ -12,8 +14,10 @@ use egui::*;
 /// stores the selected entities
 #[derive(Resource, Debug, Default, Hash)]
 pub struct SelectedModules {
-    /// the entity (TODO: entities) we have selected
+    /// the entity we have selected, the one the widgets and inspector are for
     pub selected: Option<Entity>,
+    /// everything else thats selected along with it, moves / rotates / deletes as one
+    pub group: Vec<Entity>,
     /// whether we are selecting it or placing it
     pub place: bool,
 }
@@ -22,6 +26,17 @@ impl SelectedModules {
     pub fn from_entity(entity: Entity) -> Self {
         Self {
             selected: Some(entity),
+            group: vec![],
+            place: false,
+        }
+    }
+
+    /// select all of these, the first one being the main one
+    pub fn from_entities(entities: impl IntoIterator<Item = Entity>) -> Self {
+        let mut iter = entities.into_iter();
+        Self {
+            selected: iter.next(),
+            group: iter.collect(),
             place: false,
         }
     }
@@ -29,12 +44,48 @@ impl SelectedModules {
     pub fn place_entity(entity: Entity) -> Self {
         Self {
             selected: Some(entity),
+            group: vec![],
             place: true,
         }
     }
 
     pub fn clear_selected(&mut self) {
         self.selected = None;
+        self.group.clear();
+    }
+
+    /// every selected entity, the main one first
+    pub fn all(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.selected.into_iter().chain(self.group.iter().copied())
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.all().any(|e| e == entity)
+    }
+
+    /// unselect this entity if its selected
+    pub fn remove(&mut self, entity: Entity) {
+        self.group.retain(|&e| e != entity);
+        if self.selected == Some(entity) {
+            self.selected = self.group.pop();
+        }
+    }
+
+    /// add the entity to the selection, or take it out if its already in there
+    pub fn toggle(&mut self, entity: Entity) {
+        if self.contains(entity) {
+            self.remove(entity);
+        } else if let Some(main) = self.selected.replace(entity) {
+            self.group.push(main);
+        }
+    }
+
+    /// make this entity (which should already be selected) the main one
+    pub fn focus(&mut self, entity: Entity) {
+        self.group.retain(|&e| e != entity);
+        if let Some(main) = self.selected.replace(entity) && main != entity {
+            self.group.push(main);
+        }
     }
 }
 
@@ -43,7 +94,9 @@ pub fn inspector_ui(
     selected: Res<SelectedModules>,
     mut q_module: Query<&mut ModuleComponent>,
     events: EventWriter<modules::ModuleEvent>,
-    q_module_state: Query<&ModuleState>
+    mut config_events: EventWriter<ModuleConfigChanged>,
+    q_module_state: Query<&ModuleState>,
+    q_editor_id: Query<(Entity, &EditorId)>
 ) {
     if selected.place {
         return;
@@ -52,13 +105,47 @@ pub fn inspector_ui(
         return;
     };
 
-    let mut module = q_module.get_mut(selected).unwrap();
+    // teleporters that wont do anything get called out here, so a marble going missing isnt a mystery
+    let receivers = |channel| {
+        modules::receivers(
+            channel,
+            q_editor_id.iter().filter_map(|(e, id)| Some((e, q_module.get(e).ok()?, id)))
+        )
+    };
+    let link_warning = match q_module.get(selected).ok().and_then(|m| m.module.link()) {
+        Some(Link::Sender(c)) =>
+            match receivers(c).len() {
+                0 => Some(format!("nothing receives on channel {c}, marbles sent here are lost")),
+                1 => None,
+                n => Some(format!("{n} receivers on channel {c}, only the one placed first gets marbles")),
+            }
+        Some(Link::Receiver(c, _)) if receivers(c).first().map(|(e, _)| *e) != Some(selected) =>
+            Some(format!("another receiver on channel {c} was placed first, this one gets nothing")),
+        _ => None,
+    };
+
+    let Ok(mut module) = q_module.get_mut(selected) else {
+        return;
+    };
 
     egui::Window
         ::new(format!("{}{}", "debug ", module.ty.get_name()))
         .resizable(true)
         .collapsible(false)
         .show(egui_context.ctx_mut(), |ui| {
+            let before = config::param_values(&mut *module.module);
+            if config::config_ui(ui, &mut *module.module) {
+                config_events.send(ModuleConfigChanged {
+                    entity: selected,
+                    before,
+                    after: config::param_values(&mut *module.module),
+                });
+            }
+            if let Some(warning) = &link_warning {
+                ui.colored_label(Color32::YELLOW, warning);
+            }
+            ui.separator();
+
             let mut events = ModuleEventSender::new(events);
             events.entity(selected);
             module.module.debug_ui(ui, &mut events, q_module_state.get(selected).unwrap())
@@ -67,24 +154,166 @@ pub fn inspector_ui(
     // println!("{}", window.unwrap().response.rect.width());
 }
 
+/// the blueprint thats being put together in [`blueprint_ui`]
+#[derive(Default)]
+pub struct BlueprintDraft {
+    name: String,
+    /// (module, port index)
+    inputs: HashSet<(Entity, usize)>,
+    outputs: HashSet<(Entity, usize)>,
+}
+
+/// package up the selected modules as a blueprint, or update the one theyre a copy of
+#[allow(clippy::too_many_arguments)]
+pub fn blueprint_ui(
+    mut egui_context: ResMut<EguiContext>,
+    selected: Res<SelectedModules>,
+    blueprints: Res<Blueprints>,
+    q_blueprint: Query<&BlueprintTag>,
+    q_module: Query<&ModuleComponent>,
+    q_module_state: Query<&ModuleState>,
+    has_locked: Query<With<marker::Locked>>,
+    mut draft: Local<BlueprintDraft>,
+    mut save_events: EventWriter<SaveBlueprint>,
+    mut detach_events: EventWriter<DetachBlueprint>,
+) {
+    if selected.place {
+        return;
+    }
+    let Some(main) = selected.selected else {
+        return;
+    };
+    let tag = q_blueprint.get(main).ok();
+    if selected.group.is_empty() && tag.is_none() {
+        return;
+    }
+
+    // the copy this is a part of goes first and in order, so the blueprint lines up the same
+    let mut modules = selected
+        .all()
+        .filter(|e| has_locked.get(*e).is_err() && q_module.get(*e).is_ok())
+        .collect::<Vec<_>>();
+    modules.sort_by_key(|e| {
+        q_blueprint
+            .get(*e)
+            .ok()
+            .filter(|t| Some(t.instance) == tag.map(|t| t.instance))
+            .map_or(usize::MAX, |t| t.index)
+    });
+
+    // start over whenever the selection changes
+    if selected.is_changed() {
+        *draft = default();
+        if let Some(tag) = tag && let Some(blueprint) = blueprints.get(&tag.name) {
+            draft.name = tag.name.clone();
+            let entity = |index| {
+                modules
+                    .iter()
+                    .copied()
+                    .find(|e| q_blueprint.get(*e).map_or(false, |t| t.index == index))
+            };
+            draft.inputs = blueprint.inputs
+                .iter()
+                .filter_map(|p| Some((entity(p.module)?, p.port)))
+                .collect();
+            draft.outputs = blueprint.outputs
+                .iter()
+                .filter_map(|p| Some((entity(p.module)?, p.port)))
+                .collect();
+        }
+    }
+
+    egui::Window
+        ::new("blueprint")
+        .resizable(true)
+        .collapsible(true)
+        .show(egui_context.ctx_mut(), |ui| {
+            if let Some(tag) = tag {
+                ui.label(format!("Copy of {}", tag.name));
+            }
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                ui.add(egui::TextEdit::singleline(&mut draft.name));
+            });
+
+            ui.collapsing("Exposed ports", |ui| {
+                let draft = &mut *draft;
+                for &module in modules.iter() {
+                    let (Ok(component), Ok(state)) = (q_module.get(module), q_module_state.get(module)) else {
+                        continue;
+                    };
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(component.ty.get_name());
+                        let toggle = |ui: &mut Ui, set: &mut HashSet<_>, port, label| {
+                            let mut on = set.contains(&(module, port));
+                            if ui.checkbox(&mut on, label).changed() {
+                                if on {
+                                    set.insert((module, port));
+                                } else {
+                                    set.remove(&(module, port));
+                                }
+                            }
+                        };
+                        for i in 0..state.inputs.len() {
+                            toggle(ui, &mut draft.inputs, i, format!("in {i}"));
+                        }
+                        for i in 0..state.outputs.len() {
+                            toggle(ui, &mut draft.outputs, i, format!("out {i}"));
+                        }
+                    });
+                }
+            });
+
+            let name = draft.name
+                .trim()
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || " _-".contains(*c))
+                .collect::<String>();
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!name.is_empty(), egui::Button::new("Save blueprint")).clicked() {
+                    let sorted = |set: &HashSet<(Entity, usize)>| {
+                        let mut ports = set
+                            .iter()
+                            .copied()
+                            .filter(|(e, _)| modules.contains(e))
+                            .collect::<Vec<_>>();
+                        ports.sort_by_key(|(e, port)| (modules.iter().position(|m| m == e), *port));
+                        ports
+                    };
+                    save_events.send(SaveBlueprint {
+                        name,
+                        modules: modules.clone(),
+                        inputs: sorted(&draft.inputs),
+                        outputs: sorted(&draft.outputs),
+                    });
+                }
+                if tag.is_some() && ui.button("Detach").on_hover_text("Stop being a copy").clicked() {
+                    detach_events.send(DetachBlueprint(modules.clone()));
+                }
+            });
+        });
+}
+
 /// creates the master debug ui thing
+#[allow(clippy::too_many_arguments)]
 pub fn debug_ui(
     mut egui_context: ResMut<EguiContext>,
-    mut rapier_config: ResMut<RapierConfiguration>,
+    mut control: ResMut<SimControl>,
+    mut step_n: Local<Option<u32>>,
     q_pancam: Query<&mut PanCam>,
-    mut step: Local<bool>,
     mut prev_pancam: Local<Option<PanCam>>,
     windows: Res<bevy::prelude::Windows>,
     mut text: Local<String>,
     mut save_events: EventWriter<SaveWorld>,
     mut load_events: EventWriter<LoadWorld>,
+    mut run_events: EventWriter<RunCase>,
+    mut case: Local<usize>,
+    received: Res<ReceivedMarbles>,
+    clock: Res<SimClock>,
+    mut clipboard: ResMut<Clipboard>,
+    mut overlay: ResMut<TrajectoryOverlay>,
+    slots: Res<SaveSlots>,
 ) {
-    let active = &mut rapier_config.physics_pipeline_active;
-    if *step {
-        *active = false;
-        *step = false;
-    }
-
     let Some(window) = windows.get_primary() else {
         error!("no window on god fr");
         return;
@@ -97,21 +326,37 @@ pub fn debug_ui(
         .default_pos([window.width(), window.height()])
         .show(egui_context.ctx_mut(), |ui| {
             ui.horizontal(|ui| {
-                ui.label("Physics Pipeline");
+                ui.label("Simulation");
                 if
                     ui
-                        .button([" ⏵ ", " ⏸ "][*active as usize])
-                        .on_hover_text("Start / Stop physics")
+                        .button([" ⏸ ", " ⏵ "][control.paused as usize])
+                        .on_hover_text("Play / Pause")
                         .clicked()
                 {
-                    *active = !*active;
+                    control.toggle();
+                }
+                if ui.button(" step ").on_hover_text("Step one tick").clicked() {
+                    control.step(1);
                 }
-                if ui.button(" step ").on_hover_text("Step").clicked() {
-                    *active = true;
-                    *step = true;
+                let n = step_n.get_or_insert(60);
+                ui.add(egui::DragValue::new(n).clamp_range(1..=6000).suffix(" ticks"));
+                if ui.button(" step n ").on_hover_text("Step this many ticks").clicked() {
+                    control.step(*n);
                 }
             });
+            ui.horizontal(|ui| {
+                ui.label("Speed");
+                for speed in sim::SPEEDS {
+                    ui.selectable_value(&mut control.speed, speed, format!("{speed}x"));
+                }
+                ui.label(format!("tick {}", clock.tick()));
+            });
             pancam(&mut *prev_pancam, ui, q_pancam);
+            // only touch it when clicked so the overlay doesnt think it changed every frame
+            let mut enabled = overlay.enabled;
+            if ui.checkbox(&mut enabled, "Show all trajectories (T)").changed() {
+                overlay.enabled = enabled;
+            }
 
             ui.horizontal(|ui| {
                 ui.add(egui::TextEdit::singleline(&mut *text));
@@ -128,6 +373,46 @@ pub fn debug_ui(
                     load_events.send(LoadWorld(text.clone()));
                 }
             });
+            // every solution saved for this level
+            ui.collapsing("Saves", |ui| {
+                if let Some(report) = &slots.report {
+                    ui.label(report.as_str());
+                }
+                for name in slots.list() {
+                    if ui.button(name.as_str()).clicked() {
+                        load_events.send(LoadWorld(name));
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                *case = usize::max(*case, 1);
+                ui.label("Test case");
+                ui.add(egui::DragValue::new(&mut *case).clamp_range(1..=256));
+                if ui.button(" run ").on_hover_text("Fire this case's marbles from the level inputs").clicked() {
+                    run_events.send(RunCase(*case));
+                }
+            });
+            for (i, marbles) in received.iter().enumerate() {
+                ui.label(format!("Output #{}: {:?}", i + 1, marbles));
+            }
+
+            // ctrl+c puts modules in here, paste someone elses in here and ctrl+v them
+            ui.collapsing("Clipboard", |ui| {
+                if ui.button("Copy text").on_hover_text("Copy to the system clipboard").clicked() {
+                    ui.output().copied_text = clipboard.text.clone();
+                }
+                ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit
+                                ::multiline(&mut clipboard.text)
+                                .code_editor()
+                                .desired_width(f32::INFINITY)
+                        );
+                    });
+            });
         });
 }
 